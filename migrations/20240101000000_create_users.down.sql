@@ -0,0 +1,10 @@
+-- La subida adopta una tabla `users` que ya existiera (`IF NOT EXISTS`), así que aquí no se
+-- sabe si la creó esta migración: solo se borra vacía, nunca con datos de usuarios
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM users) THEN
+        RAISE EXCEPTION 'La tabla users tiene datos: vacíela a mano si de verdad quiere revertir su creación';
+    END IF;
+END
+$$;
+DROP TABLE users;
@@ -0,0 +1,6 @@
+-- Tabla base de usuarios usada por los handlers de /users
+CREATE TABLE IF NOT EXISTS users (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    email VARCHAR(255) NOT NULL UNIQUE
+);
@@ -0,0 +1,6 @@
+-- Fecha de alta, usada para filtrar (`created_after`) y ordenar el listado
+ALTER TABLE users ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- Índice para la búsqueda de texto completo (`q=`) sobre nombre y email
+CREATE INDEX users_search_idx ON users
+    USING GIN (to_tsvector('simple', name || ' ' || email));
@@ -0,0 +1,17 @@
+-- Hash Argon2id (formato PHC); NULL para usuarios que aún no tienen contraseña
+ALTER TABLE users ADD COLUMN password_hash TEXT;
+
+-- Refresh tokens emitidos por /auth; solo se guarda su SHA-256.
+-- Todos los tokens de una misma sesión comparten `family`: al rotar se revoca el
+-- anterior, y si se reutiliza uno revocado se revoca la familia completa.
+CREATE TABLE refresh_tokens (
+    id BIGSERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL UNIQUE,
+    family TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX refresh_tokens_family_idx ON refresh_tokens (family);
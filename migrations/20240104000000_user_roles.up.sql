@@ -0,0 +1,4 @@
+-- Rol de cada usuario: `admin` gestiona todos los usuarios, `user` solo su propio registro
+CREATE TYPE user_role AS ENUM ('user', 'admin');
+
+ALTER TABLE users ADD COLUMN role user_role NOT NULL DEFAULT 'user';
@@ -0,0 +1,8 @@
+-- Token buckets del rate limiter compartidos entre instancias.
+-- UNLOGGED: se escribe en cada petición y perder los buckets tras una caída solo
+-- reinicia los límites.
+CREATE UNLOGGED TABLE rate_limits (
+    key TEXT PRIMARY KEY,
+    tokens DOUBLE PRECISION NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,13 @@
+-- Sin la columna los usuarios eliminados volverían a estar activos, y borrarlos incumpliría
+-- su periodo de conservación: la reversión se rechaza mientras quede alguno
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM users WHERE deleted_at IS NOT NULL) THEN
+        RAISE EXCEPTION 'Hay usuarios eliminados pendientes de purga: no se puede revertir el borrado lógico';
+    END IF;
+END
+$$;
+DROP INDEX IF EXISTS users_deleted_at_idx;
+DROP INDEX IF EXISTS users_email_key;
+ALTER TABLE users ADD CONSTRAINT users_email_key UNIQUE (email);
+ALTER TABLE users DROP COLUMN IF EXISTS deleted_at;
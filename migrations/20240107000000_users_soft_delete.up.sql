@@ -0,0 +1,10 @@
+-- Borrado lógico: `DELETE /users/{id}` rellena `deleted_at` y la fila se conserva
+-- hasta que la purga periódica la elimina al cumplirse `users.retention_days`
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMPTZ;
+
+-- El email solo es único entre los usuarios no eliminados, para poder registrarlo de nuevo
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_email_key;
+CREATE UNIQUE INDEX users_email_key ON users (email) WHERE deleted_at IS NULL;
+
+-- Para la purga
+CREATE INDEX users_deleted_at_idx ON users (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS users_updated_at_idx;
+ALTER TABLE users DROP COLUMN IF EXISTS updated_by_api_key;
+ALTER TABLE users DROP COLUMN IF EXISTS created_by_api_key;
+ALTER TABLE users DROP COLUMN IF EXISTS updated_by;
+ALTER TABLE users DROP COLUMN IF EXISTS created_by;
+ALTER TABLE users DROP COLUMN IF EXISTS updated_at;
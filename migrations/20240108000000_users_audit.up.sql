@@ -0,0 +1,13 @@
+-- Metadatos de auditoría que mantiene el repositorio en cada escritura.
+-- `updated_at` permite ordenar por actividad y sincronizar de forma incremental (`updated_after`)
+ALTER TABLE users ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+UPDATE users SET updated_at = COALESCE(deleted_at, created_at);
+
+-- Usuario o API key que hizo el cambio (como mucho una de las dos columnas de cada par);
+-- las dos a NULL: el propio servidor
+ALTER TABLE users ADD COLUMN created_by INTEGER REFERENCES users (id) ON DELETE SET NULL;
+ALTER TABLE users ADD COLUMN updated_by INTEGER REFERENCES users (id) ON DELETE SET NULL;
+ALTER TABLE users ADD COLUMN created_by_api_key INTEGER REFERENCES api_keys (id) ON DELETE SET NULL;
+ALTER TABLE users ADD COLUMN updated_by_api_key INTEGER REFERENCES api_keys (id) ON DELETE SET NULL;
+
+CREATE INDEX users_updated_at_idx ON users (updated_at);
@@ -0,0 +1,334 @@
+use std::future::{ready, Ready};
+use std::path::Path;
+use std::{fs, io};
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    web, Error, FromRequest, HttpMessage, HttpRequest,
+};
+use jsonwebtoken::{
+    decode, decode_header,
+    errors::ErrorKind,
+    jwk::{AlgorithmParameters, JwkSet, KeyAlgorithm},
+    Algorithm, DecodingKey, Validation,
+};
+use serde::Deserialize;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::Modify;
+
+use crate::config::JwtConfig;
+use crate::models::Scope;
+use crate::repository::{ApiKeyRepository, RepoError};
+use crate::response::{AppError, ErrorCode};
+use crate::{logging, session};
+
+/// Nombre del esquema de seguridad en el OpenAPI.
+pub const BEARER_AUTH: &str = "bearerAuth";
+
+/// Esquema de seguridad de las API keys en el OpenAPI.
+pub const API_KEY_AUTH: &str = "apiKeyAuth";
+
+/// Cabecera con la que los clientes de servicio envían su API key.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Prefijo de las API keys emitidas, para reconocerlas (p. ej. en escáneres de secretos).
+const API_KEY_PREFIX: &str = "sk_";
+
+/// Claims que se leen del token; el resto se ignoran.
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+}
+
+/// Identidad autenticada de la petición.
+///
+/// El middleware `require_auth` la guarda en las extensiones y los handlers
+/// la reciben como extractor.
+#[derive(Debug, Clone)]
+pub struct Principal {
+    /// Claim `sub` del token, o `api-key:<id>` para las API keys
+    pub subject: String,
+    /// Scopes de la API key; `None` si se autenticó con un JWT
+    pub scopes: Option<Vec<Scope>>,
+    /// ID de la API key; `None` si se autenticó con un JWT
+    pub api_key_id: Option<i32>,
+}
+
+/// Clave con la que se puede verificar un token.
+struct VerificationKey {
+    /// `kid` de la clave, si viene de un JWKS
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// Verificador de JWT (HS256 o RS256).
+pub struct JwtVerifier {
+    keys: Vec<VerificationKey>,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+impl JwtVerifier {
+    /// Carga las claves de `secret` (HS256), `public_key` (RS256) y `jwks`.
+    ///
+    /// `issuer` y `audience` son opcionales; si se definen, se exigen en el token.
+    /// Devuelve `None` si no hay ninguna clave.
+    pub fn from_config(config: &JwtConfig) -> io::Result<Option<Self>> {
+        let mut keys = Vec::new();
+
+        if let Some(secret) = &config.secret {
+            keys.push(VerificationKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(secret.as_bytes()),
+            });
+        }
+        if let Some(path) = &config.public_key {
+            let pem = fs::read(path)?;
+            keys.push(VerificationKey {
+                kid: None,
+                algorithm: Algorithm::RS256,
+                key: DecodingKey::from_rsa_pem(&pem).map_err(|e| invalid_key(path, e))?,
+            });
+        }
+        if let Some(path) = &config.jwks {
+            let jwks: JwkSet = serde_json::from_slice(&fs::read(path)?).map_err(|e| invalid_key(path, e))?;
+            for jwk in &jwks.keys {
+                let algorithm = match (jwk.common.key_algorithm, &jwk.algorithm) {
+                    (Some(KeyAlgorithm::RS256), _) | (None, AlgorithmParameters::RSA(_)) => Algorithm::RS256,
+                    (Some(KeyAlgorithm::HS256), _) | (None, AlgorithmParameters::OctetKey(_)) => Algorithm::HS256,
+                    _ => {
+                        log::warn!(
+                            "Clave {:?} de {} ignorada: solo se admiten RS256 y HS256",
+                            jwk.common.key_id,
+                            path.display()
+                        );
+                        continue;
+                    }
+                };
+                keys.push(VerificationKey {
+                    kid: jwk.common.key_id.clone(),
+                    algorithm,
+                    key: DecodingKey::from_jwk(jwk).map_err(|e| invalid_key(path, e))?,
+                });
+            }
+        }
+
+        if keys.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            keys,
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+        }))
+    }
+
+    /// Verificador HS256 con `secret`, sin emisor ni audiencia (para las pruebas).
+    #[cfg(test)]
+    pub fn from_secret(secret: &str) -> Self {
+        Self {
+            keys: vec![VerificationKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(secret.as_bytes()),
+            }],
+            issuer: None,
+            audience: None,
+        }
+    }
+
+    /// Valida firma, expiración y, si están configurados, emisor y audiencia.
+    pub fn verify(&self, token: &str) -> Result<Principal, AppError> {
+        let header = decode_header(token).map_err(|_| invalid_token())?;
+
+        // Si el token trae `kid` solo se prueban las claves sin `kid` o con el mismo
+        let candidates = self.keys.iter().filter(|key| {
+            key.algorithm == header.alg
+                && (header.kid.is_none() || key.kid.is_none() || key.kid == header.kid)
+        });
+
+        let mut expired = false;
+        for key in candidates {
+            match decode::<Claims>(token, &key.key, &self.validation(key.algorithm)) {
+                Ok(data) => return Ok(Principal { subject: data.claims.sub, scopes: None, api_key_id: None }),
+                Err(e) if *e.kind() == ErrorKind::ExpiredSignature => expired = true,
+                Err(e) => log::debug!("Token rechazado: {}", e),
+            }
+        }
+
+        Err(if expired {
+            AppError::Unauthorized { code: ErrorCode::TokenExpired, err: "El token ha expirado".into(), details: None }
+        } else {
+            invalid_token()
+        })
+    }
+
+    fn validation(&self, algorithm: Algorithm) -> Validation {
+        let mut validation = Validation::new(algorithm);
+        // Sin marcarlos como requeridos, un token sin `iss`/`aud` pasaría la validación
+        let mut required = vec!["exp", "sub"];
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+            required.push("iss");
+        }
+        match &self.audience {
+            Some(audience) => {
+                validation.set_audience(&[audience]);
+                required.push("aud");
+            }
+            None => validation.validate_aud = false,
+        }
+        validation.set_required_spec_claims(&required);
+        validation
+    }
+}
+
+fn invalid_key(path: &Path, err: impl std::fmt::Display) -> io::Error {
+    io::Error::other(format!("Clave inválida en {}: {}", path.display(), err))
+}
+
+fn invalid_token() -> AppError {
+    AppError::Unauthorized { code: ErrorCode::InvalidToken, err: "Token inválido".into(), details: None }
+}
+
+/// Genera una API key nueva y devuelve la clave junto con su prefijo visible.
+pub fn generate_api_key() -> (String, String) {
+    let key = format!("{}{}", API_KEY_PREFIX, session::random_token());
+    let prefix = key[..API_KEY_PREFIX.len() + 6].to_string();
+    (key, prefix)
+}
+
+/// Valida una API key contra el repositorio y anota su uso.
+pub async fn authenticate_api_key(keys: &dyn ApiKeyRepository, key: &str) -> Result<Principal, AppError> {
+    match keys.authenticate(&session::hash_token(key)).await {
+        Ok(api_key) => Ok(Principal {
+            subject: format!("api-key:{}", api_key.id),
+            scopes: Some(api_key.scopes),
+            api_key_id: Some(api_key.id),
+        }),
+        Err(RepoError::NotFound) => Err(AppError::Unauthorized {
+            code: ErrorCode::InvalidApiKey,
+            err: "API key inválida, revocada o caducada".into(),
+            details: None,
+        }),
+        Err(e) => {
+            log::error!("Error al validar API key: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+/// Resultado de comprobar las credenciales de la petición.
+///
+/// `authenticated` lo guarda en las extensiones para que el rate limiter y `require_auth`
+/// no las comprueben dos veces: una API key se busca (y se anota su uso) en la base de datos.
+struct Resolved(Result<Principal, AppError>);
+
+/// Comprueba el `Authorization: Bearer <jwt>` o la cabecera `X-API-Key` de la petición.
+///
+/// Si llegan ambos se usa el token.
+async fn check_credentials(req: &ServiceRequest) -> Result<Principal, AppError> {
+    let token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .map(|(_, token)| token.trim());
+
+    let api_key = req.headers().get(API_KEY_HEADER).and_then(|value| value.to_str().ok());
+
+    match (token, api_key) {
+        (Some(token), _) => match req.app_data::<web::Data<JwtVerifier>>() {
+            Some(verifier) => verifier.verify(token),
+            // Sin claves de verificación (solo en modo demo) ningún token es válido
+            None => Err(invalid_token()),
+        },
+        (None, Some(key)) => match req.app_data::<web::Data<dyn ApiKeyRepository>>() {
+            Some(keys) => authenticate_api_key(keys.get_ref(), key.trim()).await,
+            None => {
+                log::error!("ApiKeyRepository no está registrado en la aplicación");
+                Err(AppError::InternalError)
+            }
+        },
+        (None, None) => Err(AppError::unauthorized("Autenticación requerida")),
+    }
+}
+
+/// Identidad autenticada de la petición, si sus credenciales son válidas.
+///
+/// Las credenciales se comprueban una sola vez por petición; las siguientes llamadas
+/// (y `require_auth`) reutilizan el resultado.
+pub async fn authenticated(req: &ServiceRequest) -> Option<Principal> {
+    if !req.extensions().contains::<Resolved>() {
+        let resolved = Resolved(check_credentials(req).await);
+        req.extensions_mut().insert(resolved);
+    }
+    req.extensions().get::<Resolved>().and_then(|resolved| resolved.0.as_ref().ok().cloned())
+}
+
+/// Middleware que exige un `Authorization: Bearer <jwt>` o una cabecera `X-API-Key` válidos.
+///
+/// Si la credencial es correcta guarda el `Principal` en las extensiones de la petición;
+/// si no, responde 401 sin llegar al handler.
+pub async fn require_auth(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    authenticated(&req).await;
+    let principal = req
+        .extensions_mut()
+        .remove::<Resolved>()
+        .map_or(Err(AppError::InternalError), |resolved| resolved.0);
+
+    match principal {
+        Ok(principal) => {
+            logging::set_user(&principal.subject);
+            req.extensions_mut().insert(principal);
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
+        }
+        Err(err) => Ok(req.error_response(err).map_into_right_body()),
+    }
+}
+
+impl FromRequest for Principal {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        // Solo falta si la ruta no pasa por `require_auth`
+        ready(
+            req.extensions()
+                .get::<Principal>()
+                .cloned()
+                .ok_or_else(|| AppError::unauthorized("Autenticación requerida")),
+        )
+    }
+}
+
+/// Añade los esquemas `bearerAuth` (JWT) y `apiKeyAuth` (`X-API-Key`) al OpenAPI
+/// para que Swagger UI pueda enviar credenciales.
+pub struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            BEARER_AUTH,
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+        );
+        components.add_security_scheme(
+            API_KEY_AUTH,
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "X-API-Key",
+                "API key de servicio, creada por un administrador en /api-keys",
+            ))),
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use std::io;
+use std::path::PathBuf;
+
+/// Subcomandos aceptados por el binario `api`.
+pub enum Command {
+    /// Aplica las migraciones pendientes y arranca el servidor HTTP (por defecto).
+    ///
+    /// Con `--demo` no se conecta a la base de datos y guarda los usuarios en memoria.
+    Serve { demo: bool },
+    /// Aplica las migraciones pendientes y termina.
+    Migrate,
+    /// Revierte las últimas `steps` migraciones aplicadas.
+    Rollback { steps: usize },
+    /// Muestra qué migraciones están aplicadas.
+    Status,
+}
+
+/// Valores de configuración indicados en la línea de comandos; tienen prioridad
+/// sobre el fichero y las variables de entorno.
+#[derive(Debug, Default)]
+pub struct Overrides {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub workers: Option<usize>,
+    pub log_level: Option<String>,
+    /// `text` o `json`
+    pub log_format: Option<String>,
+    /// `dev` o `prod`; lo valida la carga de la configuración
+    pub mode: Option<String>,
+    pub no_swagger: bool,
+}
+
+/// Línea de comandos ya interpretada.
+pub struct Cli {
+    pub command: Command,
+    /// Fichero indicado con `--config`
+    pub config: Option<PathBuf>,
+    pub overrides: Overrides,
+}
+
+impl Cli {
+    /// Interpreta los argumentos de línea de comandos (sin el nombre del programa).
+    ///
+    /// Las opciones pueden ir antes o después del subcomando.
+    pub fn parse<I>(args: I) -> io::Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter();
+        let mut positional = Vec::new();
+        let mut demo = false;
+        let mut config = None;
+        let mut overrides = Overrides::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--demo" => demo = true,
+                "--no-swagger" => overrides.no_swagger = true,
+                "--config" => config = Some(PathBuf::from(value(&mut args, &arg)?)),
+                "--host" => overrides.host = Some(value(&mut args, &arg)?),
+                "--port" => overrides.port = Some(parsed(&mut args, &arg)?),
+                "--workers" => overrides.workers = Some(parsed(&mut args, &arg)?),
+                "--log-level" => overrides.log_level = Some(value(&mut args, &arg)?),
+                "--log-format" => overrides.log_format = Some(value(&mut args, &arg)?),
+                "--mode" => overrides.mode = Some(value(&mut args, &arg)?),
+                other if other.starts_with("--") => return Err(unexpected(other)),
+                _ => positional.push(arg),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let command = match positional.next().as_deref() {
+            // `api --demo` es un atajo de `api serve --demo`
+            None | Some("serve") => Command::Serve { demo },
+            Some("migrate") => Command::Migrate,
+            Some("rollback") => {
+                let steps = match positional.next() {
+                    Some(steps) => steps.parse().map_err(|_| {
+                        invalid(format!("Número de pasos inválido para rollback: {}", steps))
+                    })?,
+                    None => 1,
+                };
+                Command::Rollback { steps }
+            }
+            Some("status") => Command::Status,
+            Some(other) => return Err(invalid(format!("Subcomando desconocido: {}\n{}", other, USAGE))),
+        };
+
+        if let Some(extra) = positional.next() {
+            return Err(unexpected(&extra));
+        }
+        if demo && !matches!(command, Command::Serve { .. }) {
+            return Err(unexpected("--demo"));
+        }
+
+        Ok(Self { command, config, overrides })
+    }
+}
+
+const USAGE: &str = "Uso: api [serve [--demo] | migrate | rollback [pasos] | status] [opciones]\n\
+Opciones: --config <fichero> --mode <dev|prod> --host <host> --port <puerto> --workers <n> \
+--log-level <nivel> --log-format <text|json> --no-swagger";
+
+/// Valor de una opción (`--port 9000`).
+fn value(args: &mut impl Iterator<Item = String>, flag: &str) -> io::Result<String> {
+    args.next()
+        .filter(|value| !value.starts_with("--"))
+        .ok_or_else(|| invalid(format!("Falta el valor de {}\n{}", flag, USAGE)))
+}
+
+fn parsed<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> io::Result<T> {
+    let raw = value(args, flag)?;
+    raw.parse().map_err(|_| invalid(format!("Valor inválido para {}: {}", flag, raw)))
+}
+
+fn unexpected(arg: &str) -> io::Error {
+    invalid(format!("Argumento inesperado: {}\n{}", arg, USAGE))
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> io::Result<Cli> {
+        Cli::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn sin_argumentos_arranca_el_servidor() {
+        let cli = parse(&[]).unwrap();
+        assert!(matches!(cli.command, Command::Serve { demo: false }));
+        assert!(cli.config.is_none());
+
+        assert!(matches!(parse(&["--demo"]).unwrap().command, Command::Serve { demo: true }));
+    }
+
+    #[test]
+    fn acepta_opciones_antes_y_despues_del_subcomando() {
+        let cli = parse(&["--port", "9000", "serve", "--config", "api.toml", "--no-swagger", "--log-format", "json"])
+            .unwrap();
+        assert!(matches!(cli.command, Command::Serve { demo: false }));
+        assert_eq!(cli.config, Some(PathBuf::from("api.toml")));
+        assert_eq!(cli.overrides.port, Some(9000));
+        assert_eq!(cli.overrides.log_format.as_deref(), Some("json"));
+        assert!(cli.overrides.no_swagger);
+    }
+
+    #[test]
+    fn rollback_admite_el_numero_de_pasos() {
+        assert!(matches!(parse(&["rollback"]).unwrap().command, Command::Rollback { steps: 1 }));
+        assert!(matches!(parse(&["rollback", "3"]).unwrap().command, Command::Rollback { steps: 3 }));
+        assert!(parse(&["rollback", "tres"]).is_err());
+    }
+
+    #[test]
+    fn rechaza_argumentos_invalidos() {
+        for args in [
+            &["deploy"][..],
+            &["migrate", "--demo"],
+            &["status", "extra"],
+            &["--verbose"],
+            &["--port"],
+            &["--port", "--demo"],
+            &["--port", "http"],
+        ] {
+            let err = parse(args).err().unwrap_or_else(|| panic!("{:?} debería fallar", args));
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
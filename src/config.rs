@@ -0,0 +1,665 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use figment::{
+    providers::{Env, Format, Serialized, Toml},
+    Figment,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::Overrides;
+
+/// Fichero de configuración que se lee si existe y no se indica otro con `--config`.
+pub const DEFAULT_CONFIG_FILE: &str = "api.toml";
+
+/// Prefijo de las variables de entorno de configuración; `__` separa secciones
+/// (p. ej. `APP_SERVER__PORT=9000`).
+const ENV_PREFIX: &str = "APP_";
+
+/// Claves de primer nivel de `Config`. Solo estas se leen de las variables `APP_*`, para
+/// que otras del entorno (p. ej. `APP_ENV`) no choquen con `deny_unknown_fields`.
+const ENV_KEYS: &[&str] = &[
+    "mode", "server", "database", "log", "swagger", "health", "metrics", "tracing", "users", "jwt", "rate_limit",
+    "errors",
+];
+
+/// Configuración del servidor.
+///
+/// Se combina, de menor a mayor prioridad: valores por defecto, fichero TOML,
+/// variables `APP_*` y opciones de línea de comandos.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub mode: Mode,
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub log: LogConfig,
+    pub swagger: SwaggerConfig,
+    pub health: HealthConfig,
+    pub metrics: MetricsConfig,
+    pub tracing: TracingConfig,
+    pub users: UsersConfig,
+    pub jwt: JwtConfig,
+    pub rate_limit: RateLimitConfig,
+    pub errors: ErrorsConfig,
+}
+
+/// Modo de ejecución; decide los valores por defecto de la documentación.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Swagger UI abierta y se lanza el navegador al arrancar
+    #[default]
+    Dev,
+    /// Sin Swagger UI ni navegador; si se habilita la documentación, exige credenciales
+    Prod,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Hilos de trabajo de actix; por defecto, uno por núcleo
+    pub workers: Option<usize>,
+    /// Prefijo con el que un proxy inverso publica la API (p. ej. `/api`), que lo elimina
+    /// antes de reenviar la petición. Se usa en las URLs de la documentación.
+    pub base_path: String,
+    /// Espera tras la señal de apagado, ya sin estar listo, antes de dejar de aceptar
+    /// conexiones; da tiempo al balanceador a retirar la instancia
+    pub drain_delay_secs: u64,
+    /// Máximo que se espera a las peticiones en curso y, después, a los workers en segundo plano
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            workers: None,
+            base_path: String::new(),
+            drain_delay_secs: 0,
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn drain_delay(&self) -> Duration {
+        Duration::from_secs(self.drain_delay_secs)
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// Si no se define se usa `DATABASE_URL`
+    pub url: Option<String>,
+    pub min_connections: u32,
+    pub max_connections: u32,
+    /// Espera máxima para obtener una conexión del pool
+    pub acquire_timeout_secs: u64,
+    /// Tiempo que una conexión libre sigue abierta; sin valor no se cierra
+    pub idle_timeout_secs: Option<u64>,
+    /// Vida máxima de una conexión; sin valor no se recicla
+    pub max_lifetime_secs: Option<u64>,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            min_connections: 0,
+            max_connections: 5,
+            acquire_timeout_secs: 30,
+            idle_timeout_secs: Some(10 * 60),
+            max_lifetime_secs: Some(30 * 60),
+        }
+    }
+}
+
+impl DatabaseConfig {
+    pub fn acquire_timeout(&self) -> Duration {
+        Duration::from_secs(self.acquire_timeout_secs)
+    }
+
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout_secs.map(Duration::from_secs)
+    }
+
+    pub fn max_lifetime(&self) -> Option<Duration> {
+        self.max_lifetime_secs.map(Duration::from_secs)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Filtro con la sintaxis de `RUST_LOG`, p. ej. `info` o `info,sqlx=warn`
+    pub level: String,
+    /// Niveles por módulo que se añaden a `level`, p. ej. `{ "api::ratelimit" = "debug" }`
+    pub modules: BTreeMap<String, String>,
+    /// Por defecto `text` en `dev` y `json` en `prod`
+    pub format: Option<LogFormat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Una línea legible por registro
+    Text,
+    /// Un objeto JSON por línea
+    Json,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self { level: "info,sqlx=warn".to_string(), modules: BTreeMap::new(), format: None }
+    }
+}
+
+impl LogConfig {
+    /// `level` seguido de los niveles de `modules`, que tienen prioridad.
+    pub fn filters(&self) -> String {
+        self.modules.iter().fold(self.level.clone(), |filters, (module, level)| {
+            format!("{},{}={}", filters, module, level)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    /// Máximo para cada comprobación de `/health` y `/health/ready`
+    pub timeout_ms: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self { timeout_ms: 2000 }
+    }
+}
+
+impl HealthConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Publica las métricas para Prometheus; se recogen igualmente aunque no se publiquen
+    pub enabled: bool,
+    pub path: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self { enabled: true, path: "/metrics".to_string() }
+    }
+}
+
+/// Trazas distribuidas con OpenTelemetry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TracingConfig {
+    pub exporter: TraceExporter,
+    /// URL OTLP/HTTP del colector; sin valor, `OTEL_EXPORTER_OTLP_ENDPOINT` o `http://localhost:4318`
+    pub endpoint: Option<String>,
+    /// Fichero de `exporter = "file"`
+    pub file: Option<PathBuf>,
+    /// Atributo `service.name` de las trazas
+    pub service_name: String,
+    /// Fracción de trazas nuevas que se muestrean; las que llegan con `traceparent` siguen su decisión
+    pub sample_ratio: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceExporter {
+    /// Sin exportar; solo se propaga el contexto recibido
+    None,
+    /// Protocolo OTLP sobre HTTP
+    Otlp,
+    /// Un span JSON por línea en la salida estándar
+    Stdout,
+    /// Un span JSON por línea en `file`
+    File,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            exporter: TraceExporter::None,
+            endpoint: None,
+            file: None,
+            service_name: "api".to_string(),
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+/// Conservación de los usuarios eliminados.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UsersConfig {
+    /// Días que se conserva un usuario eliminado antes de borrarlo definitivamente
+    pub retention_days: u32,
+    /// Cada cuánto se buscan usuarios que han superado `retention_days`
+    pub purge_interval_secs: u64,
+}
+
+impl Default for UsersConfig {
+    fn default() -> Self {
+        Self { retention_days: 30, purge_interval_secs: 60 * 60 }
+    }
+}
+
+impl UsersConfig {
+    pub fn retention(&self) -> chrono::Duration {
+        chrono::Duration::days(i64::from(self.retention_days))
+    }
+
+    pub fn purge_interval(&self) -> Duration {
+        Duration::from_secs(self.purge_interval_secs)
+    }
+}
+
+/// Claves y validez de los JWT.
+///
+/// Para verificar tokens hace falta al menos una de `secret`, `public_key` o `jwks`; para
+/// emitirlos en `/auth`, `private_key` o `secret`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtConfig {
+    /// Secreto compartido para HS256; si no se define se usa `JWT_SECRET`
+    pub secret: Option<String>,
+    /// Clave pública RSA en PEM para verificar tokens RS256
+    pub public_key: Option<PathBuf>,
+    /// Fichero JWKS local con claves RS256/HS256 identificadas por `kid`
+    pub jwks: Option<PathBuf>,
+    /// Clave privada RSA en PEM para firmar con RS256; tiene prioridad sobre `secret`
+    pub private_key: Option<PathBuf>,
+    /// `kid` de la cabecera de los tokens emitidos
+    pub key_id: Option<String>,
+    /// Si se definen, se ponen en los tokens emitidos y se exigen en los recibidos
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    pub access_ttl_secs: u64,
+    pub refresh_ttl_secs: u64,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            secret: None,
+            public_key: None,
+            jwks: None,
+            private_key: None,
+            key_id: None,
+            issuer: None,
+            audience: None,
+            access_ttl_secs: 15 * 60,
+            refresh_ttl_secs: 30 * 24 * 60 * 60,
+        }
+    }
+}
+
+/// Límites de peticiones por cliente.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// `GET`/`HEAD` sobre `/users` y `/api-keys`
+    pub read: LimitConfig,
+    /// Escrituras sobre `/users` y `/api-keys`
+    pub write: LimitConfig,
+    /// `/auth/*`: login, refresh y logout
+    pub auth: LimitConfig,
+    /// Usar la IP de `Forwarded`/`X-Forwarded-For`; solo detrás de un proxy inverso de confianza
+    pub trust_proxy: bool,
+    pub store: RateLimitStoreKind,
+}
+
+/// `requests` peticiones repuestas a lo largo de `period_secs`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LimitConfig {
+    pub requests: u32,
+    pub period_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitStoreKind {
+    /// Cada instancia lleva sus propios contadores
+    #[default]
+    Memory,
+    /// Contadores compartidos entre instancias en la base de datos (no se usa en `--demo`)
+    Postgres,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            read: LimitConfig { requests: 300, period_secs: 60 },
+            write: LimitConfig { requests: 30, period_secs: 60 },
+            auth: LimitConfig { requests: 10, period_secs: 60 },
+            trust_proxy: false,
+            store: RateLimitStoreKind::Memory,
+        }
+    }
+}
+
+impl LimitConfig {
+    pub fn period(&self) -> Duration {
+        Duration::from_secs(self.period_secs)
+    }
+}
+
+/// Formato de las respuestas de error.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErrorsConfig {
+    pub problem_details: ProblemDetailsMode,
+}
+
+/// Cuándo se responden los errores como Problem Details (RFC 7807).
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProblemDetailsMode {
+    /// Solo si el cliente envía `Accept: application/problem+json`
+    #[default]
+    Negotiate,
+    /// Siempre, sin importar la cabecera `Accept`
+    Always,
+}
+
+/// Swagger UI y documento OpenAPI. Las opciones sin valor dependen de `mode`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SwaggerConfig {
+    /// Publica Swagger UI y el documento OpenAPI; por defecto, solo en `dev`
+    pub enabled: Option<bool>,
+    /// Abre Swagger UI en el navegador al arrancar; por defecto, solo en `dev`
+    pub open_browser: Option<bool>,
+    /// Exige HTTP Basic con `username` y `password`; por defecto, solo en `prod`
+    pub require_auth: Option<bool>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Ruta de Swagger UI
+    pub path: String,
+    /// Ruta del documento OpenAPI
+    pub openapi_path: String,
+}
+
+impl Default for SwaggerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: None,
+            open_browser: None,
+            require_auth: None,
+            username: None,
+            password: None,
+            path: "/swagger-ui".to_string(),
+            openapi_path: "/api-docs/openapi.json".to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// Carga la configuración por capas.
+    ///
+    /// `path` es el fichero indicado con `--config`, que debe existir; sin él se lee
+    /// `api.toml` solo si existe.
+    pub fn load(path: Option<&Path>, overrides: &Overrides) -> io::Result<Self> {
+        let file = match path {
+            Some(path) if !path.is_file() => {
+                return Err(config_error(format!("no existe el fichero {}", path.display())));
+            }
+            Some(path) => path,
+            None => Path::new(DEFAULT_CONFIG_FILE),
+        };
+
+        let mut figment = Figment::from(Serialized::defaults(Config::default()))
+            .merge(Toml::file(file))
+            // `DATABASE_URL` es la variable habitual de sqlx y de otras herramientas
+            .merge(Env::raw().only(&["DATABASE_URL"]).map(|_| "database.url".into()))
+            // Así el secreto puede quedarse fuera del fichero, como en `.env`
+            .merge(Env::raw().only(&["JWT_SECRET"]).map(|_| "jwt.secret".into()))
+            .merge(Env::prefixed(ENV_PREFIX).split("__").filter(|key| {
+                let section = key.as_str().split('.').next().unwrap_or_default();
+                ENV_KEYS.iter().any(|known| known.eq_ignore_ascii_case(section))
+            }));
+
+        if let Some(host) = &overrides.host {
+            figment = figment.merge(Serialized::default("server.host", host));
+        }
+        if let Some(port) = overrides.port {
+            figment = figment.merge(Serialized::default("server.port", port));
+        }
+        if let Some(workers) = overrides.workers {
+            figment = figment.merge(Serialized::default("server.workers", workers));
+        }
+        if let Some(level) = &overrides.log_level {
+            figment = figment.merge(Serialized::default("log.level", level));
+        }
+        if let Some(format) = &overrides.log_format {
+            figment = figment.merge(Serialized::default("log.format", format));
+        }
+        if let Some(mode) = &overrides.mode {
+            figment = figment.merge(Serialized::default("mode", mode));
+        }
+        if overrides.no_swagger {
+            figment = figment.merge(Serialized::default("swagger.enabled", false));
+        }
+
+        let config: Config = figment.extract().map_err(|e| config_error(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> io::Result<()> {
+        let db = &self.database;
+        if db.max_connections == 0 {
+            return Err(config_error("database.max_connections debe ser mayor que 0".to_string()));
+        }
+        if db.min_connections > db.max_connections {
+            return Err(config_error(format!(
+                "database.min_connections ({}) no puede superar a database.max_connections ({})",
+                db.min_connections, db.max_connections
+            )));
+        }
+        if self.server.workers == Some(0) {
+            return Err(config_error("server.workers debe ser mayor que 0".to_string()));
+        }
+        let tracing = &self.tracing;
+        if !(0.0..=1.0).contains(&tracing.sample_ratio) {
+            return Err(config_error(format!(
+                "tracing.sample_ratio debe estar entre 0 y 1: {}",
+                tracing.sample_ratio
+            )));
+        }
+        if tracing.exporter == TraceExporter::File && tracing.file.is_none() {
+            return Err(config_error("tracing.file es obligatorio con exporter = \"file\"".to_string()));
+        }
+        if self.health.timeout_ms == 0 {
+            return Err(config_error("health.timeout_ms debe ser mayor que 0".to_string()));
+        }
+        if self.users.purge_interval_secs == 0 {
+            return Err(config_error("users.purge_interval_secs debe ser mayor que 0".to_string()));
+        }
+        if self.jwt.access_ttl_secs == 0 || self.jwt.refresh_ttl_secs == 0 {
+            return Err(config_error(
+                "jwt.access_ttl_secs y jwt.refresh_ttl_secs deben ser mayores que 0".to_string(),
+            ));
+        }
+        let limits = &self.rate_limit;
+        for (key, limit) in [("read", &limits.read), ("write", &limits.write), ("auth", &limits.auth)] {
+            if limit.requests == 0 || limit.period_secs == 0 {
+                return Err(config_error(format!(
+                    "rate_limit.{} necesita requests y period_secs mayores que 0",
+                    key
+                )));
+            }
+        }
+
+        let base_path = &self.server.base_path;
+        if !base_path.is_empty() && (!base_path.starts_with('/') || base_path.ends_with('/')) {
+            return Err(config_error(format!(
+                "server.base_path debe empezar por / y no terminar en /: {}",
+                base_path
+            )));
+        }
+        let swagger = &self.swagger;
+        let paths = [
+            ("swagger.path", &swagger.path),
+            ("swagger.openapi_path", &swagger.openapi_path),
+            ("metrics.path", &self.metrics.path),
+        ];
+        for (key, path) in paths {
+            if path.len() < 2 || !path.starts_with('/') || path.ends_with('/') {
+                return Err(config_error(format!("{} debe empezar por / y no terminar en /: {}", key, path)));
+            }
+        }
+        if self.swagger_enabled()
+            && self.docs_require_auth()
+            && (swagger.username.is_none() || swagger.password.is_none())
+        {
+            return Err(config_error(
+                "la documentación exige autenticación: defina swagger.username y swagger.password \
+                 o desactívela con swagger.require_auth = false"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Si se publican Swagger UI y el documento OpenAPI.
+    pub fn swagger_enabled(&self) -> bool {
+        self.swagger.enabled.unwrap_or(self.mode == Mode::Dev)
+    }
+
+    /// Si se abre el navegador al arrancar.
+    pub fn open_browser(&self) -> bool {
+        self.swagger.open_browser.unwrap_or(self.mode == Mode::Dev)
+    }
+
+    pub fn log_format(&self) -> LogFormat {
+        self.log.format.unwrap_or(match self.mode {
+            Mode::Dev => LogFormat::Text,
+            Mode::Prod => LogFormat::Json,
+        })
+    }
+
+    /// Si la documentación exige credenciales.
+    pub fn docs_require_auth(&self) -> bool {
+        self.swagger.require_auth.unwrap_or(self.mode == Mode::Prod)
+    }
+
+    /// Dirección `host:puerto` en la que escucha el servidor.
+    pub fn bind_address(&self) -> (&str, u16) {
+        (&self.server.host, self.server.port)
+    }
+}
+
+fn config_error(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("Configuración inválida: {}", msg))
+}
+
+#[cfg(test)]
+// El tipo de error de los cierres lo impone `Jail::expect_with`
+#[allow(clippy::result_large_err)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    fn load(jail: &Jail, overrides: &Overrides) -> io::Result<Config> {
+        // `Jail` cambia el directorio actual a uno temporal, donde no hay `api.toml`
+        assert!(!jail.directory().join(DEFAULT_CONFIG_FILE).exists());
+        Config::load(None, overrides)
+    }
+
+    #[test]
+    fn combina_fichero_entorno_y_linea_de_comandos() {
+        Jail::expect_with(|jail| {
+            jail.create_file(DEFAULT_CONFIG_FILE, "mode = \"prod\"\n[server]\nport = 9000\nhost = \"0.0.0.0\"")?;
+            jail.set_env("APP_SERVER__PORT", "9100");
+            jail.set_env("DATABASE_URL", "postgres://localhost/test");
+
+            let overrides = Overrides { host: Some("127.0.0.2".into()), ..Default::default() };
+            let config = Config::load(None, &overrides).unwrap();
+            assert_eq!(config.mode, Mode::Prod);
+            assert_eq!(config.bind_address(), ("127.0.0.2", 9100));
+            assert_eq!(config.database.url.as_deref(), Some("postgres://localhost/test"));
+            assert_eq!(config.log_format(), LogFormat::Json);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn ignora_variables_app_de_otras_herramientas() {
+        Jail::expect_with(|jail| {
+            jail.set_env("APP_ENV", "staging");
+            jail.set_env("APP_VERSION", "1.2.3");
+            assert!(load(jail, &Overrides::default()).is_ok());
+
+            // Un error tipográfico dentro de una sección conocida sí se detecta
+            jail.set_env("APP_SERVER__PROT", "9000");
+            assert!(load(jail, &Overrides::default()).is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn valida_los_valores_combinados() {
+        Jail::expect_with(|jail| {
+            let overrides = Overrides { workers: Some(0), ..Default::default() };
+            assert!(load(jail, &overrides).is_err());
+
+            jail.set_env("APP_DATABASE__MIN_CONNECTIONS", "50");
+            let err = load(jail, &Overrides::default()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn lee_jwt_y_limites_de_peticiones() {
+        Jail::expect_with(|jail| {
+            jail.create_file(DEFAULT_CONFIG_FILE, "[rate_limit]\nwrite = { requests = 5, period_secs = 10 }")?;
+            jail.set_env("JWT_SECRET", "secreto");
+            jail.set_env("APP_JWT__ISSUER", "https://auth.example.com");
+            jail.set_env("APP_RATE_LIMIT__STORE", "postgres");
+            jail.set_env("APP_ERRORS__PROBLEM_DETAILS", "always");
+
+            let config = Config::load(None, &Overrides::default()).unwrap();
+            assert_eq!(config.jwt.secret.as_deref(), Some("secreto"));
+            assert_eq!(config.jwt.issuer.as_deref(), Some("https://auth.example.com"));
+            assert_eq!(config.rate_limit.write.requests, 5);
+            assert_eq!(config.rate_limit.read.requests, 300);
+            assert_eq!(config.rate_limit.store, RateLimitStoreKind::Postgres);
+            assert_eq!(config.errors.problem_details, ProblemDetailsMode::Always);
+
+            jail.set_env("APP_RATE_LIMIT__AUTH__PERIOD_SECS", "0");
+            assert!(Config::load(None, &Overrides::default()).is_err());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn exige_que_exista_el_fichero_indicado() {
+        Jail::expect_with(|_| {
+            assert!(Config::load(Some(Path::new("no-existe.toml")), &Overrides::default()).is_err());
+            Ok(())
+        });
+    }
+}
@@ -1,12 +1,20 @@
 use sqlx::postgres::PgPoolOptions;
-use dotenv::dotenv;
-use std::env;
+use std::io;
+
+use crate::config::DatabaseConfig;
+
+pub async fn get_db_pool(config: &DatabaseConfig) -> io::Result<sqlx::PgPool> {
+    let database_url = config.url.as_deref().ok_or_else(|| {
+        io::Error::other("Falta la URL de la base de datos: defina DATABASE_URL, APP_DATABASE__URL o database.url")
+    })?;
 
-pub async fn get_db_pool() -> Result<sqlx::PgPool, sqlx::Error> {
-    dotenv().ok();
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&database_url)
+        .min_connections(config.min_connections)
+        .max_connections(config.max_connections)
+        .acquire_timeout(config.acquire_timeout())
+        .idle_timeout(config.idle_timeout())
+        .max_lifetime(config.max_lifetime())
+        .connect(database_url)
         .await
-}
\ No newline at end of file
+        .map_err(|e| io::Error::other(format!("No se pudo conectar a la base de datos: {}", e)))
+}
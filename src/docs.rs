@@ -0,0 +1,101 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    middleware::Next,
+    web, Error, HttpResponse,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256};
+use utoipa::openapi::{OpenApi, Server};
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::config::Config;
+
+/// Rutas de la documentación y, si se exigen, sus credenciales.
+#[derive(Clone)]
+pub struct DocsAccess {
+    /// Ruta de Swagger UI
+    path: String,
+    /// Ruta del documento OpenAPI
+    openapi_path: String,
+    /// SHA-256 de `usuario:contraseña`; `None` si la documentación es pública
+    credentials: Option<[u8; 32]>,
+}
+
+impl DocsAccess {
+    pub fn from_config(config: &Config) -> Self {
+        let swagger = &config.swagger;
+        let credentials = match (config.docs_require_auth(), &swagger.username, &swagger.password) {
+            (true, Some(username), Some(password)) => Some(digest(&format!("{}:{}", username, password))),
+            _ => None,
+        };
+        Self { path: swagger.path.clone(), openapi_path: swagger.openapi_path.clone(), credentials }
+    }
+
+    fn covers(&self, path: &str) -> bool {
+        path == self.openapi_path
+            || path.strip_prefix(self.path.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    /// Compara las credenciales de `Authorization: Basic`.
+    fn accepts(&self, req: &ServiceRequest) -> bool {
+        let Some(expected) = &self.credentials else {
+            return true;
+        };
+        req.headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("basic"))
+            .and_then(|(_, encoded)| STANDARD.decode(encoded.trim()).ok())
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            // Se comparan los resúmenes para no revelar la longitud ni el prefijo correcto
+            .is_some_and(|credentials| digest(&credentials) == *expected)
+    }
+}
+
+fn digest(value: &str) -> [u8; 32] {
+    Sha256::digest(value.as_bytes()).into()
+}
+
+/// Middleware que pide HTTP Basic para Swagger UI y el documento OpenAPI.
+///
+/// El resto de rutas no se ven afectadas; la API sigue usando sus propios tokens.
+pub async fn require_auth(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let denied = req
+        .app_data::<web::Data<DocsAccess>>()
+        .is_some_and(|access| access.covers(req.path()) && !access.accepts(&req));
+    if !denied {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    }
+
+    log::debug!("Acceso a la documentación sin credenciales válidas: {}", req.path());
+    let res = HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, r#"Basic realm="API docs", charset="UTF-8""#))
+        .finish();
+    Ok(req.into_response(res).map_into_right_body())
+}
+
+/// Documento OpenAPI tal como se publica: detrás de un proxy, "Try it out" debe
+/// llamar a las rutas con el prefijo público.
+pub fn openapi(mut openapi: OpenApi, base_path: &str) -> OpenApi {
+    if !base_path.is_empty() {
+        openapi.servers = Some(vec![Server::new(base_path)]);
+    }
+    openapi
+}
+
+/// Swagger UI en las rutas configuradas.
+///
+/// La UI se ejecuta en el navegador, así que pide el documento OpenAPI con el prefijo público.
+pub fn swagger_ui(config: &Config, openapi: OpenApi) -> SwaggerUi {
+    let swagger = &config.swagger;
+    let public_url = format!("{}{}", config.server.base_path, swagger.openapi_path);
+    SwaggerUi::new(format!("{}/{{_:.*}}", swagger.path))
+        .url(swagger.openapi_path.clone(), openapi)
+        .config(utoipa_swagger_ui::Config::from(public_url))
+}
@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use crate::migrations;
+use crate::shutdown::Shutdown;
+
+/// Estado de la instancia o de una dependencia.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    Down,
+    /// La dependencia no se usa (p. ej. la base de datos en modo demo)
+    Disabled,
+}
+
+/// Resultado de comprobar una dependencia.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthCheck {
+    pub status: HealthStatus,
+    /// Duración de la comprobación en milisegundos
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1.7)]
+    pub latency_ms: Option<f64>,
+    /// Motivo del fallo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Estado global de la instancia: `down` si alguna dependencia lo está.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    /// Comprobaciones por dependencia: `server`, `database` y `migrations`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<HashMap<String, HealthCheck>>)]
+    pub checks: Option<BTreeMap<&'static str, HealthCheck>>,
+}
+
+impl HealthCheck {
+    fn new(status: HealthStatus) -> Self {
+        Self { status, latency_ms: None, error: None }
+    }
+
+    fn down(error: impl Into<String>) -> Self {
+        Self { error: Some(error.into()), ..Self::new(HealthStatus::Down) }
+    }
+}
+
+impl HealthReport {
+    /// Respuesta de `/health/live`: si el proceso contesta, está vivo.
+    pub fn alive() -> Self {
+        Self { status: HealthStatus::Up, checks: None }
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.status != HealthStatus::Down
+    }
+
+    /// Quita el detalle de cada dependencia.
+    pub fn summary(self) -> Self {
+        Self { checks: None, ..self }
+    }
+}
+
+/// Comprobaciones de las dependencias de las que depende servir peticiones.
+pub struct Health {
+    /// `None` en modo demo
+    pool: Option<PgPool>,
+    /// Máximo por comprobación; una dependencia lenta cuenta como caída
+    timeout: Duration,
+}
+
+impl Health {
+    pub fn new(pool: Option<PgPool>, timeout: Duration) -> Self {
+        Self { pool, timeout }
+    }
+
+    /// Comprueba todas las dependencias a la vez.
+    pub async fn report(&self, shutdown: &Shutdown) -> HealthReport {
+        // Durante el apagado la instancia deja de estar lista aunque todo responda
+        let server = if shutdown.is_ready() {
+            HealthCheck::new(HealthStatus::Up)
+        } else {
+            HealthCheck::down("El servidor se está apagando")
+        };
+        let (database, migrations) = match &self.pool {
+            Some(pool) => tokio::join!(self.check(database(pool)), self.check(pending_migrations(pool))),
+            None => (HealthCheck::new(HealthStatus::Disabled), HealthCheck::new(HealthStatus::Disabled)),
+        };
+
+        let checks = BTreeMap::from([("server", server), ("database", database), ("migrations", migrations)]);
+        let status = if checks.values().any(|check| check.status == HealthStatus::Down) {
+            HealthStatus::Down
+        } else {
+            HealthStatus::Up
+        };
+        HealthReport { status, checks: Some(checks) }
+    }
+
+    /// Ejecuta una comprobación midiendo su latencia y cortándola si supera `timeout`.
+    async fn check(&self, probe: impl Future<Output = Result<(), String>>) -> HealthCheck {
+        let start = Instant::now();
+        let mut check = match tokio::time::timeout(self.timeout, probe).await {
+            Ok(Ok(())) => HealthCheck::new(HealthStatus::Up),
+            Ok(Err(e)) => HealthCheck::down(e),
+            Err(_) => HealthCheck::down(format!("Sin respuesta en {} ms", self.timeout.as_millis())),
+        };
+        check.latency_ms = Some(start.elapsed().as_secs_f64() * 1000.0);
+        check
+    }
+}
+
+/// Obtiene una conexión del pool y ejecuta `SELECT 1`.
+async fn database(pool: &PgPool) -> Result<(), String> {
+    sqlx::query("SELECT 1").execute(pool).await.map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// Falla si alguna migración del binario no está aplicada.
+async fn pending_migrations(pool: &PgPool) -> Result<(), String> {
+    let pending: Vec<_> = migrations::status(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|migration| !migration.applied)
+        .map(|migration| migration.version.to_string())
+        .collect();
+
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Migraciones pendientes: {}", pending.join(", ")))
+    }
+}
@@ -0,0 +1,276 @@
+//! Catálogo de mensajes de error en español, inglés y portugués.
+//!
+//! Los textos se eligen por `ErrorCode` (o por el código de la regla de validación)
+//! y sus marcadores `{nombre}` se rellenan con los `details` del error.
+
+use actix_web::{http::header::ACCEPT_LANGUAGE, HttpRequest};
+use serde_json::Value;
+use validator::ValidationError;
+
+use crate::response::ErrorCode;
+
+/// Idiomas soportados.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Locale {
+    #[default]
+    Es,
+    En,
+    Pt,
+}
+
+impl Locale {
+    /// Etiqueta para la cabecera `Content-Language`.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Self::Es => "es",
+            Self::En => "en",
+            Self::Pt => "pt",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.split('-').next().unwrap_or_default();
+        [Self::Es, Self::En, Self::Pt]
+            .into_iter()
+            .find(|locale| primary.eq_ignore_ascii_case(locale.tag()))
+    }
+
+    /// Elige el idioma según `Accept-Language`, respetando los pesos `q`.
+    ///
+    /// Si ningún idioma pedido está soportado se usa español.
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let Some(header) = req.headers().get(ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok()) else {
+            return Self::default();
+        };
+
+        let mut ranges: Vec<(&str, f32)> = header
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';').map(str::trim);
+                let tag = parts.next().filter(|tag| !tag.is_empty())?;
+                let q = parts
+                    .find_map(|param| param.strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.parse().ok())?;
+                Some((tag, q))
+            })
+            .filter(|(_, q)| *q > 0.0)
+            .collect();
+        // Orden estable: a igual peso manda el orden de la cabecera
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        ranges
+            .into_iter()
+            .find_map(|(tag, _)| Self::from_tag(tag))
+            .unwrap_or_default()
+    }
+}
+
+/// Mensaje de un error en el idioma pedido.
+///
+/// Devuelve `None` si el código no tiene texto en el catálogo (el mensaje lo da
+/// quien crea el error) o si a `details` le falta algún dato de la plantilla.
+pub fn error_message(code: ErrorCode, locale: Locale, details: Option<&Value>) -> Option<String> {
+    interpolate(error_template(code, locale)?, |name| details?.get(name))
+}
+
+/// Mensaje de una regla de validación incumplida, con sus parámetros (`min`, `max`...).
+pub fn violation_message(error: &ValidationError, locale: Locale) -> String {
+    violation_template(&error.code, locale)
+        .and_then(|template| interpolate(template, |name| error.params.get(name)))
+        .or_else(|| error.message.as_ref().map(|m| m.to_string()))
+        .unwrap_or_else(|| error.code.to_string())
+}
+
+/// Sustituye cada `{nombre}` de la plantilla; las listas se unen con comas.
+fn interpolate<'a>(template: &str, param: impl Fn(&str) -> Option<&'a Value>) -> Option<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let end = start + rest[start..].find('}')?;
+        out.push_str(&rest[..start]);
+        out.push_str(&display(param(&rest[start + 1..end])?));
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+
+    Some(out)
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(display).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+fn error_template(code: ErrorCode, locale: Locale) -> Option<&'static str> {
+    use ErrorCode::*;
+
+    let text = match locale {
+        Locale::Es => match code {
+            BadRequest => return None,
+            InvalidQuery => "Parámetros de consulta inválidos: {reason}",
+            InvalidBody => "Cuerpo JSON inválido: {reason}",
+            InvalidCursor => "Cursor inválido",
+            InvalidSort => "Campo de ordenación no permitido: {field}",
+            InvalidPatch => "{format} inválido: {reason}",
+            UnsupportedMediaType => "Content-Type no soportado: use {supported}",
+            MissingFields => "Campos requeridos: {fields}",
+            ValidationFailed => "Datos de entrada inválidos",
+            Unauthorized => "Autenticación requerida",
+            InvalidToken => "Token inválido",
+            TokenExpired => "El token ha expirado",
+            InvalidCredentials => "Email o contraseña incorrectos",
+            InvalidRefreshToken => "Refresh token inválido o expirado",
+            InvalidApiKey => "API key inválida, revocada o caducada",
+            Forbidden => "No tiene permisos para esta operación",
+            NotFound => "Recurso no encontrado",
+            UserNotFound => "Usuario {id} no encontrado",
+            ApiKeyNotFound => "API key {id} no encontrada",
+            Conflict => "El recurso ya existe",
+            EmailTaken => "El email {email} ya está registrado",
+            PatchFailed => "No se pudo aplicar el JSON Patch: {reason}",
+            ImmutableField => "El campo {field} no se puede modificar",
+            RateLimited => "Demasiadas peticiones, intente de nuevo más tarde",
+            InternalError => "500 error interno del servidor",
+            DatabaseUnavailable => "Base de datos no disponible",
+        },
+        Locale::En => match code {
+            BadRequest => return None,
+            InvalidQuery => "Invalid query parameters: {reason}",
+            InvalidBody => "Invalid JSON body: {reason}",
+            InvalidCursor => "Invalid cursor",
+            InvalidSort => "Sort field not allowed: {field}",
+            InvalidPatch => "Invalid {format}: {reason}",
+            UnsupportedMediaType => "Unsupported Content-Type: use {supported}",
+            MissingFields => "Required fields: {fields}",
+            ValidationFailed => "Invalid input data",
+            Unauthorized => "Authentication required",
+            InvalidToken => "Invalid token",
+            TokenExpired => "The token has expired",
+            InvalidCredentials => "Invalid email or password",
+            InvalidRefreshToken => "Invalid or expired refresh token",
+            InvalidApiKey => "Invalid, revoked or expired API key",
+            Forbidden => "You are not allowed to perform this operation",
+            NotFound => "Resource not found",
+            UserNotFound => "User {id} not found",
+            ApiKeyNotFound => "API key {id} not found",
+            Conflict => "The resource already exists",
+            EmailTaken => "The email {email} is already registered",
+            PatchFailed => "The JSON Patch could not be applied: {reason}",
+            ImmutableField => "The field {field} cannot be modified",
+            RateLimited => "Too many requests, please try again later",
+            InternalError => "500 internal server error",
+            DatabaseUnavailable => "Database unavailable",
+        },
+        Locale::Pt => match code {
+            BadRequest => return None,
+            InvalidQuery => "Parâmetros de consulta inválidos: {reason}",
+            InvalidBody => "Corpo JSON inválido: {reason}",
+            InvalidCursor => "Cursor inválido",
+            InvalidSort => "Campo de ordenação não permitido: {field}",
+            InvalidPatch => "{format} inválido: {reason}",
+            UnsupportedMediaType => "Content-Type não suportado: use {supported}",
+            MissingFields => "Campos obrigatórios: {fields}",
+            ValidationFailed => "Dados de entrada inválidos",
+            Unauthorized => "Autenticação necessária",
+            InvalidToken => "Token inválido",
+            TokenExpired => "O token expirou",
+            InvalidCredentials => "Email ou senha incorretos",
+            InvalidRefreshToken => "Refresh token inválido ou expirado",
+            InvalidApiKey => "API key inválida, revogada ou expirada",
+            Forbidden => "Você não tem permissão para esta operação",
+            NotFound => "Recurso não encontrado",
+            UserNotFound => "Usuário {id} não encontrado",
+            ApiKeyNotFound => "API key {id} não encontrada",
+            Conflict => "O recurso já existe",
+            EmailTaken => "O email {email} já está cadastrado",
+            PatchFailed => "Não foi possível aplicar o JSON Patch: {reason}",
+            ImmutableField => "O campo {field} não pode ser modificado",
+            RateLimited => "Muitas requisições, tente novamente mais tarde",
+            InternalError => "500 erro interno do servidor",
+            DatabaseUnavailable => "Banco de dados indisponível",
+        },
+    };
+
+    Some(text)
+}
+
+fn violation_template(code: &str, locale: Locale) -> Option<&'static str> {
+    let text = match (code, locale) {
+        ("length", Locale::Es) => "Debe tener entre {min} y {max} caracteres",
+        ("length", Locale::En) => "Must be between {min} and {max} characters long",
+        ("length", Locale::Pt) => "Deve ter entre {min} e {max} caracteres",
+        ("email", Locale::Es) => "Formato de email inválido",
+        ("email", Locale::En) => "Invalid email format",
+        ("email", Locale::Pt) => "Formato de email inválido",
+        ("control_chars", Locale::Es) => "No puede contener caracteres de control",
+        ("control_chars", Locale::En) => "Must not contain control characters",
+        ("control_chars", Locale::Pt) => "Não pode conter caracteres de controle",
+        ("password_weak", Locale::Es) => "Debe contener al menos una letra y un dígito",
+        ("password_weak", Locale::En) => "Must contain at least one letter and one digit",
+        ("password_weak", Locale::Pt) => "Deve conter pelo menos uma letra e um dígito",
+        ("future", Locale::Es) => "Debe ser una fecha futura",
+        ("future", Locale::En) => "Must be a date in the future",
+        ("future", Locale::Pt) => "Deve ser uma data futura",
+        ("not_empty", Locale::Es) => "Debe contener al menos un elemento",
+        ("not_empty", Locale::En) => "Must contain at least one item",
+        ("not_empty", Locale::Pt) => "Deve conter pelo menos um item",
+        _ => return None,
+    };
+
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use serde_json::json;
+
+    use super::*;
+
+    fn locale(header: &str) -> Locale {
+        Locale::from_request(&TestRequest::default().insert_header((ACCEPT_LANGUAGE, header)).to_http_request())
+    }
+
+    #[test]
+    fn from_request_respeta_los_pesos() {
+        assert_eq!(locale("en-US"), Locale::En);
+        assert_eq!(locale("es;q=0.5, pt-BR;q=0.8"), Locale::Pt);
+        // A igual peso manda el orden de la cabecera
+        assert_eq!(locale("pt, en"), Locale::Pt);
+    }
+
+    #[test]
+    fn from_request_usa_espanol_por_defecto() {
+        assert_eq!(Locale::from_request(&TestRequest::default().to_http_request()), Locale::Es);
+        assert_eq!(locale("fr, de;q=0.9"), Locale::Es);
+        assert_eq!(locale("en;q=0, fr"), Locale::Es);
+        assert_eq!(locale("en;q=abc"), Locale::Es);
+    }
+
+    #[test]
+    fn interpolate_rellena_los_marcadores() {
+        let details = json!({ "id": 7, "allowed": ["id", "name"] });
+        let param = |name: &str| details.get(name);
+
+        assert_eq!(interpolate("Usuario {id}", param).as_deref(), Some("Usuario 7"));
+        assert_eq!(interpolate("Use {allowed}", param).as_deref(), Some("Use id, name"));
+        assert_eq!(interpolate("Sin marcadores", param).as_deref(), Some("Sin marcadores"));
+        // Falta el dato o la plantilla está mal cerrada
+        assert_eq!(interpolate("Email {email}", param), None);
+        assert_eq!(interpolate("Usuario {id", param), None);
+    }
+
+    #[test]
+    fn error_message_usa_el_idioma_pedido() {
+        let details = json!({ "id": 7 });
+        assert_eq!(
+            error_message(ErrorCode::UserNotFound, Locale::En, Some(&details)).as_deref(),
+            Some("User 7 not found")
+        );
+        assert_eq!(error_message(ErrorCode::UserNotFound, Locale::En, None), None);
+    }
+}
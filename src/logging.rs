@@ -0,0 +1,162 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web, Error,
+};
+use chrono::{SecondsFormat, Utc};
+use log::kv::{self, VisitSource};
+use serde_json::{Map, Value};
+
+use crate::config::{Config, LogFormat};
+use crate::metrics::MetricsPath;
+use crate::{request_id, telemetry};
+
+/// Target de las líneas del access log.
+const ACCESS_TARGET: &str = "access";
+
+tokio::task_local! {
+    /// Petición que se está atendiendo en la tarea actual.
+    static REQUEST: Arc<RequestContext>;
+}
+
+/// Datos de la petición que se añaden a cada línea de log emitida mientras se atiende.
+struct RequestContext {
+    method: String,
+    path: String,
+    /// Sujeto autenticado; lo rellena `auth::require_auth`
+    user_id: Mutex<Option<String>>,
+}
+
+/// Instala el logger global según `[log]`.
+///
+/// En formato `json` cada línea es un objeto con `timestamp`, `level`, `target`,
+/// `message`, los datos de la petición en curso y los campos propios del registro.
+pub fn init(config: &Config) {
+    let mut builder = env_logger::Builder::new();
+    builder.parse_filters(&config.log.filters());
+    match config.log_format() {
+        LogFormat::Json => builder.format(|buf, record| {
+            let line = serde_json::to_string(&json_record(record)).map_err(std::io::Error::other)?;
+            writeln!(buf, "{}", line)
+        }),
+        LogFormat::Text => builder.format(|buf, record| {
+            write!(buf, "[{} {:<5} {}] {}", timestamp(), record.level(), record.target(), record.args())?;
+            for (key, value) in fields(record) {
+                // Las cadenas van sin comillas
+                let value = value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string());
+                write!(buf, " {}={}", key, value)?;
+            }
+            writeln!(buf)
+        }),
+    };
+    builder.init();
+}
+
+/// Anota el sujeto autenticado en el contexto de la petición en curso.
+pub fn set_user(subject: &str) {
+    let _ = REQUEST.try_with(|request| {
+        *request.user_id.lock().unwrap_or_else(|e| e.into_inner()) = Some(subject.to_string());
+    });
+}
+
+/// Middleware que abre el contexto de la petición y escribe una línea de access log al terminar.
+///
+/// Las líneas usan el target `access` (se desactivan con `access=off`). Las sondas de
+/// `/health` y de la ruta de métricas (`MetricsPath`) se registran en nivel `debug` para
+/// no llenar el log.
+pub async fn access_log(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let context = Arc::new(RequestContext {
+        method: req.method().to_string(),
+        path: req.path().to_string(),
+        user_id: Mutex::new(None),
+    });
+    let is_metrics = req
+        .app_data::<web::Data<MetricsPath>>()
+        .is_some_and(|metrics_path| metrics_path.0 == context.path);
+    let level = if context.path.starts_with("/health") || is_metrics {
+        log::Level::Debug
+    } else {
+        log::Level::Info
+    };
+
+    let start = Instant::now();
+    REQUEST
+        .scope(context.clone(), async move {
+            let res = next.call(req).await;
+            let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+            let status = match &res {
+                Ok(res) => res.status().as_u16(),
+                Err(e) => e.as_response_error().status_code().as_u16(),
+            };
+            log::log!(
+                target: ACCESS_TARGET, level, status = status, latency_ms = latency_ms;
+                "{} {} {} {:.1}ms", context.method, context.path, status, latency_ms
+            );
+            res
+        })
+        .await
+}
+
+fn timestamp() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn json_record(record: &log::Record) -> Map<String, Value> {
+    let mut line = Map::new();
+    line.insert("timestamp".to_string(), timestamp().into());
+    line.insert("level".to_string(), record.level().as_str().into());
+    line.insert("target".to_string(), record.target().into());
+    line.insert("message".to_string(), record.args().to_string().into());
+    line.extend(fields(record));
+    line
+}
+
+/// Datos de la petición en curso seguidos de los campos clave-valor del registro.
+fn fields(record: &log::Record) -> Map<String, Value> {
+    let mut fields = Map::new();
+    if let Some(request_id) = request_id::current() {
+        fields.insert("request_id".to_string(), request_id.into());
+    }
+    if let Some(trace_id) = telemetry::current_trace_id() {
+        fields.insert("trace_id".to_string(), trace_id.into());
+    }
+    let _ = REQUEST.try_with(|request| {
+        fields.insert("method".to_string(), request.method.as_str().into());
+        fields.insert("path".to_string(), request.path.as_str().into());
+        if let Some(user_id) = &*request.user_id.lock().unwrap_or_else(|e| e.into_inner()) {
+            fields.insert("user_id".to_string(), user_id.as_str().into());
+        }
+    });
+    let _ = record.key_values().visit(&mut Fields(&mut fields));
+    fields
+}
+
+/// Convierte los campos clave-valor de `log` en valores JSON.
+struct Fields<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(number) = value.to_u64() {
+            number.into()
+        } else if let Some(number) = value.to_i64() {
+            number.into()
+        } else if let Some(number) = value.to_f64() {
+            // Tres decimales bastan para las latencias en milisegundos
+            ((number * 1000.0).round() / 1000.0).into()
+        } else if let Some(flag) = value.to_bool() {
+            flag.into()
+        } else {
+            value.to_string().into()
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
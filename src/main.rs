@@ -1,15 +1,62 @@
+mod auth;
+mod cli;
+mod config;
 mod models;
+mod pagination;
 mod db;
+mod docs;
+mod health;
+mod i18n;
+mod logging;
+mod metrics;
+mod migrations;
+mod password;
+mod permissions;
+mod patch;
+mod problem;
+mod query;
+mod ratelimit;
+mod repository;
+mod request_id;
 mod response;
+mod retention;
+mod session;
+mod shutdown;
+mod telemetry;
+mod validation;
 
-use actix_web::{test::status_service, web, App, HttpServer, Responder};
-use models::{User, CreateUser, UpdateUser, DeleteUser};
-use response::{AppError, AppResponse, OkModel};
-use serde::de::value::Error;
+use actix_web::{http::header::{CACHE_CONTROL, LINK}, middleware, web, App, HttpRequest, HttpResponse, HttpServer};
+use auth::{JwtVerifier, SecuritySchemes};
+use cli::{Cli, Command};
+use config::{Config, RateLimitStoreKind};
+use health::{Health, HealthCheck, HealthReport, HealthStatus};
+use metrics::{Metrics, MetricsPath};
+use chrono::Utc;
+use models::{
+    ApiKey, CreateApiKey, CreatedApiKey, CreateUser, DeleteUser, LoginRequest, RefreshRequest, Role, Scope, TokenResponse,
+    UpdateUser, User, UserListQuery, UserQuery, UserSortField,
+};
+use pagination::{BasePath, PageInfo, PageQuery};
+use patch::UserPatch;
+use permissions::{Authorization, Permission};
+use problem::{ErrorResponses, ProblemConfig, ProblemDetails};
+use query::{Sort, SortField};
+use ratelimit::RateLimiter;
+use repository::{
+    ApiKeyRepository, Consumed, InMemoryApiKeyRepository, InMemoryRateLimitStore, InMemoryTokenRepository,
+    InMemoryUserRepository, Instrumented, NewApiKey, PgApiKeyRepository, PgRateLimitStore, PgTokenRepository, PgUserRepository,
+    RateLimitStore, RepoError, TokenRepository, UserRepository,
+};
+use response::{ApiKeyList, AppError, AppResponse, AppResult, ErrModel, ErrorCode, OkModel, UserPage};
+use serde_json::json;
+use session::TokenIssuer;
+use shutdown::Shutdown;
 use sqlx::PgPool;
-use std::sync::OnceLock;
-use utoipa::OpenApi;
-use utoipa_swagger_ui::SwaggerUi;
+use std::process::ExitCode;
+use std::{env, io};
+use std::sync::{Arc, OnceLock};
+use utoipa::openapi::{ContentBuilder, Ref};
+use utoipa::{Modify, OpenApi};
 
 #[derive(OpenApi)]
 #[openapi(
@@ -18,22 +65,85 @@ use utoipa_swagger_ui::SwaggerUi;
         get_user,
         create_user,
         update_user,
-        delete_user
+        patch_user,
+        delete_user,
+        restore_user,
+        login,
+        refresh,
+        logout,
+        list_api_keys,
+        create_api_key,
+        revoke_api_key,
+        health,
+        liveness,
+        readiness
     ),
     components(
         schemas(
             User,
+            Role,
             CreateUser,
             UpdateUser,
-            DeleteUser
+            DeleteUser,
+            LoginRequest,
+            RefreshRequest,
+            TokenResponse,
+            ApiKey,
+            ApiKeyList,
+            CreateApiKey,
+            CreatedApiKey,
+            Scope,
+            HealthReport,
+            HealthCheck,
+            HealthStatus,
+            UserPage,
+            PageInfo,
+            ErrModel,
+            ErrorCode,
+            ProblemDetails,
+            json_patch::Patch,
+            json_patch::PatchOperation,
+            json_patch::AddOperation,
+            json_patch::RemoveOperation,
+            json_patch::ReplaceOperation,
+            json_patch::MoveOperation,
+            json_patch::CopyOperation,
+            json_patch::TestOperation
         )
     ),
+    modifiers(&JsonPatchContent, &SecuritySchemes, &ErrorResponses),
     tags(
-        (name = "Users", description = "API de usuarios")
+        (name = "Users", description = "API de usuarios"),
+        (name = "Auth", description = "Inicio de sesión y emisión de tokens"),
+        (name = "API keys", description = "Claves de acceso para clientes de servicio"),
+        (name = "Health", description = "Estado del servicio para orquestadores y balanceadores")
     )
 )]
 struct ApiDoc;
 
+/// Añade `application/json-patch+json` al cuerpo de `PATCH /users/{id}`.
+///
+/// `#[utoipa::path]` solo admite un Content-Type por `request_body`.
+struct JsonPatchContent;
+
+impl Modify for JsonPatchContent {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let body = openapi
+            .paths
+            .paths
+            .get_mut("/users/{id}")
+            .and_then(|item| item.operations.get_mut(&utoipa::openapi::PathItemType::Patch))
+            .and_then(|op| op.request_body.as_mut());
+
+        if let Some(body) = body {
+            body.content.insert(
+                patch::JSON_PATCH.to_string(),
+                ContentBuilder::new().schema(Ref::from_schema_name("Patch")).build(),
+            );
+        }
+    }
+}
+
 static OPENAPI: OnceLock<utoipa::openapi::OpenApi> = OnceLock::new();
 
 // Obtener todos los usuarios
@@ -41,20 +151,49 @@ static OPENAPI: OnceLock<utoipa::openapi::OpenApi> = OnceLock::new();
     get,
     path = "/users",
     tag = "Users",
+    security(("bearerAuth" = []), ("apiKeyAuth" = [])),
+    params(PageQuery, UserListQuery),
     responses(
-        (status = 200, body = Vec<User>, description = "List of users"),
-        (status = 500, description = "Internal server error")
+        (status = 200, body = UserPage, description = "Page of users",
+            headers(("Link" = String, description = "Enlaces first/prev/next (RFC 8288)"))),
+        (status = 400, description = "Bad request"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 429, description = "Too many requests",
+            headers(("Retry-After" = u64, description = "Segundos hasta poder reintentar"))),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Database unavailable")
     )
 )]
-async fn get_users(pool: web::Data<PgPool>) -> Result<web::Json<OkModel<Vec<User>>>, AppError> {
-    let users = sqlx::query_as::<_, User>("SELECT id, name, email FROM users")
-        .fetch_all(pool.get_ref())
-        .await?;  // El operador ? convierte automáticamente sqlx::Error a AppError
-        
-    Ok(web::Json(OkModel {
-        success: true,
-        data: users,
-    }))
+async fn get_users(
+    repo: web::Data<dyn UserRepository>,
+    auth: Authorization,
+    req: HttpRequest,
+    page_query: web::Query<PageQuery>,
+    list_query: web::Query<UserListQuery>,
+) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::ListUsers)?;
+    if list_query.include_deleted {
+        auth.require(Permission::ReadDeletedUsers)?;
+    }
+    let sort = Sort::parse(list_query.sort.as_deref(), UserSortField::Id).map_err(|field| {
+        let allowed: Vec<&str> = UserSortField::ALLOWED.iter().map(|(name, _)| *name).collect();
+        AppError::invalid(ErrorCode::InvalidSort, format!("Campo de ordenación no permitido: {}", field))
+            .with_details(json!({ "field": field, "allowed": allowed }))
+    })?;
+    let filter = list_query.filter();
+    let page_request = page_query.to_request(|key| sort.accepts(key))?;
+
+    let page = repo.list(&filter, &sort, &page_request).await?;  // El operador ? convierte automáticamente RepoError a AppError
+    let info = PageInfo::new(&page_request, &page);
+
+    Ok(HttpResponse::Ok()
+        .insert_header((LINK, info.link_header(&req)))
+        .json(OkModel {
+            success: true,
+            data: page.items,
+            pagination: Some(info),
+        }))
 }
 
 // Obtener un usuario por ID
@@ -62,34 +201,49 @@ async fn get_users(pool: web::Data<PgPool>) -> Result<web::Json<OkModel<Vec<User
     get,
     path = "/users/{id}",
     tag = "Users",
+    security(("bearerAuth" = []), ("apiKeyAuth" = [])),
     responses(
         (status = 200, body = User),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Insufficient permissions"),
         (status = 404, description = "User not found"),
-        (status = 500, description = "Internal server error")
+        (status = 429, description = "Too many requests",
+            headers(("Retry-After" = u64, description = "Segundos hasta poder reintentar"))),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Database unavailable")
     ),
     params(
-        ("id" = i32, description = "User ID")
+        ("id" = i32, description = "User ID"),
+        UserQuery
     )
 )]
 async fn get_user(
-    pool: web::Data<PgPool>,
+    repo: web::Data<dyn UserRepository>,
+    auth: Authorization,
     user_id: web::Path<i32>,
-) -> Result<web::Json<OkModel<User>>, AppError> {
-    let user = sqlx::query_as::<_, User>("SELECT id, name, email FROM users WHERE id = $1")
-        .bind(user_id.into_inner())
-        .fetch_one(pool.get_ref())
-        .await
+    query: web::Query<UserQuery>,
+) -> AppResult<User> {
+    let user_id = user_id.into_inner();
+    auth.require(Permission::ReadUser(user_id))?;
+    let user = if query.include_deleted {
+        auth.require(Permission::ReadDeletedUsers)?;
+        repo.get_any(user_id).await
+    } else {
+        repo.get(user_id).await
+    };
+    let user = user
         .map_err(|e| match e {
-            sqlx::Error::RowNotFound => AppError::Invalid { err: "Usuario no encontrado" },
+            RepoError::NotFound => user_not_found(user_id),
             _ => {
                 log::error!("Error de base de datos: {}", e);
-                AppError::InternalError
+                e.into()
             }
         })?;
 
     Ok(web::Json(OkModel {
         success: true,
         data: user,
+        pagination: None,
     }))
 }
 
@@ -98,54 +252,46 @@ async fn get_user(
     post,
     path = "/users",
     tag = "Users",
+    security(("bearerAuth" = []), ("apiKeyAuth" = [])),
     request_body = CreateUser,
     responses(
         (status = 201, body = User),
         (status = 400, description = "Bad request"),
-        (status = 500, description = "Internal server error")
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 409, description = "Email already registered"),
+        (status = 429, description = "Too many requests",
+            headers(("Retry-After" = u64, description = "Segundos hasta poder reintentar"))),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Database unavailable")
     )
 )]
 async fn create_user(
-    pool: web::Data<PgPool>,
+    repo: web::Data<dyn UserRepository>,
+    auth: Authorization,
     new_user: web::Json<CreateUser>,
-) -> Result<web::Json<OkModel<User>>, AppError> {
-    // 1. Validación básica del input
-    if new_user.name.is_empty() || new_user.email.is_empty() {
-        return Err(AppError::Invalid {
-            err: "Nombre y email son requeridos",
-        });
-    }
+) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::CreateUser)?;
 
-    // 2. Validación de formato de email (ejemplo simple)
-    if !new_user.email.contains('@') {
-        return Err(AppError::Invalid {
-            err: "Formato de email inválido",
-        });
-    }
+    // 1. Validación y normalización del input
+    let new_user = validation::validate(new_user.into_inner())?;
+    let password_hash = password::hash_optional(new_user.password.as_deref()).await?;
 
-    // 3. Ejecutar la consulta con manejo de errores
-    match sqlx::query_as::<_, User>(
-        "INSERT INTO users (name, email) VALUES ($1, $2) RETURNING id, name, email"
-    )
-    .bind(&new_user.name)
-    .bind(&new_user.email)
-    .fetch_one(pool.get_ref())
-    .await
-    {
-        Ok(user) => Ok(web::Json(OkModel {
+    // 2. Ejecutar la consulta con manejo de errores
+    match repo.create(&new_user, password_hash.as_deref(), auth.actor()).await {
+        Ok(user) => Ok(HttpResponse::Created().json(OkModel {
             success: true,
             data: user,
+            pagination: None,
         })),
-        Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+        Err(RepoError::Duplicate) => {
             // Violación de constraint UNIQUE (email duplicado)
-            Err(AppError::Invalid {
-                err: "El email ya está registrado",
-            })
+            Err(email_taken(&new_user.email))
         }
         Err(e) => {
             // Registrar error inesperado
             log::error!("Error al crear usuario: {}", e);
-            Err(AppError::InternalError)
+            Err(e.into())
         }
     }
 }
@@ -155,67 +301,143 @@ async fn create_user(
     put,
     path = "/users/{id}",
     tag = "Users",
+    security(("bearerAuth" = []), ("apiKeyAuth" = [])),
     request_body = CreateUser,
     responses(
         (status = 200, body = User),
+        (status = 400, description = "Bad request"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Insufficient permissions"),
         (status = 404, description = "User not found"),
-        (status = 500, description = "Internal server error")
+        (status = 409, description = "Email already registered"),
+        (status = 429, description = "Too many requests",
+            headers(("Retry-After" = u64, description = "Segundos hasta poder reintentar"))),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Database unavailable")
     ),
     params(
         ("id" = i32, description = "User ID")
     )
 )]
 async fn update_user(
-    pool: web::Data<PgPool>,
+    repo: web::Data<dyn UserRepository>,
+    auth: Authorization,
     user_id: web::Path<i32>,
     updated_user: web::Json<CreateUser>,
-) -> Result<web::Json<OkModel<User>>, AppError> {
+) -> AppResult<User> {
     let user_id = user_id.into_inner();
+    auth.require(Permission::UpdateUser(user_id))?;
 
-    // 1. Validación de los datos de entrada
-    if updated_user.name.is_empty() || updated_user.email.is_empty() {
-        return Err(AppError::Invalid {
-            err: "Nombre y email son requeridos",
-        });
-    }
-
-    // 2. Validación básica de formato de email
-    if !updated_user.email.contains('@') {
-        return Err(AppError::Invalid {
-            err: "Formato de email inválido",
-        });
-    }
+    // 1. Validación y normalización de los datos de entrada
+    let updated_user = validation::validate(updated_user.into_inner())?;
+    let current = match repo.get(user_id).await {
+        Ok(user) => user,
+        Err(RepoError::NotFound) => return Err(user_not_found(user_id)),
+        Err(e) => {
+            log::error!("Error al obtener usuario {}: {}", user_id, e);
+            return Err(e.into());
+        }
+    };
+    auth.require_update(&current, updated_user.password.is_some())?;
+    let password_hash = password::hash_optional(updated_user.password.as_deref()).await?;
 
-    // 3. Ejecutar la actualización con manejo de errores
-    match sqlx::query_as::<_, User>(
-        "UPDATE users SET name = $1, email = $2 WHERE id = $3 RETURNING id, name, email"
-    )
-    .bind(&updated_user.name)
-    .bind(&updated_user.email)
-    .bind(user_id)
-    .fetch_one(pool.get_ref())
-    .await
-    {
+    // 2. Ejecutar la actualización con manejo de errores
+    match repo.update(user_id, &updated_user, password_hash.as_deref(), auth.actor()).await {
         Ok(user) => Ok(web::Json(OkModel {
             success: true,
             data: user,
+            pagination: None,
         })),
-        Err(sqlx::Error::RowNotFound) => {
+        Err(RepoError::NotFound) => {
             // Usuario no encontrado
-            Err(AppError::Invalid {
-                err: "Usuario no encontrado",
-            })
+            Err(user_not_found(user_id))
         },
-        Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+        Err(RepoError::Duplicate) => {
             // Email ya existe
-            Err(AppError::Invalid {
-                err: "El email ya está registrado por otro usuario",
-            })
+            Err(email_taken(&updated_user.email))
         },
         Err(e) => {
             // Error inesperado de base de datos
             log::error!("Error al actualizar usuario {}: {}", user_id, e);
-            Err(AppError::InternalError)
+            Err(e.into())
+        }
+    }
+}
+
+// Actualizar parcialmente un usuario
+#[utoipa::path(
+    patch,
+    path = "/users/{id}",
+    tag = "Users",
+    security(("bearerAuth" = []), ("apiKeyAuth" = [])),
+    request_body(
+        content = UpdateUser,
+        content_type = "application/merge-patch+json",
+        description = "JSON Merge Patch (RFC 7396) o JSON Patch (RFC 6902) según el Content-Type"
+    ),
+    responses(
+        (status = 200, body = User),
+        (status = 400, description = "Bad request"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "User not found"),
+        (status = 409, description = "Email already registered"),
+        (status = 422, description = "Patch cannot be applied"),
+        (status = 429, description = "Too many requests",
+            headers(("Retry-After" = u64, description = "Segundos hasta poder reintentar"))),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Database unavailable")
+    ),
+    params(
+        ("id" = i32, description = "User ID")
+    )
+)]
+async fn patch_user(
+    repo: web::Data<dyn UserRepository>,
+    auth: Authorization,
+    user_id: web::Path<i32>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> AppResult<User> {
+    let user_id = user_id.into_inner();
+    auth.require(Permission::UpdateUser(user_id))?;
+    let user_patch = UserPatch::from_request(&req, &body)?;
+
+    // 1. Aplicar el parche sobre el estado actual y validar el resultado completo
+    let current = match repo.get(user_id).await {
+        Ok(user) => user,
+        Err(RepoError::NotFound) => return Err(user_not_found(user_id)),
+        Err(e) => {
+            log::error!("Error al obtener usuario {}: {}", user_id, e);
+            return Err(e.into());
+        }
+    };
+    let patched = validation::validate(user_patch.apply(&current)?)?;
+
+    // 2. Guardar solo los campos que cambian
+    let changes = patch::changes(&current, patched);
+    auth.require_update(&current, changes.password.is_some())?;
+    if changes.name.is_none() && changes.email.is_none() && changes.password.is_none() {
+        return AppResponse::Success(current).response();
+    }
+    let password_hash = password::hash_optional(changes.password.as_deref()).await?;
+
+    match repo.patch(user_id, &changes, password_hash.as_deref(), auth.actor()).await {
+        Ok(user) => Ok(web::Json(OkModel {
+            success: true,
+            data: user,
+            pagination: None,
+        })),
+        Err(RepoError::NotFound) => {
+            // Eliminado entre la lectura y la escritura
+            Err(user_not_found(user_id))
+        },
+        Err(RepoError::Duplicate) => {
+            Err(email_taken(changes.email.as_deref().unwrap_or_default()))
+        },
+        Err(e) => {
+            log::error!("Error al actualizar parcialmente usuario {}: {}", user_id, e);
+            Err(e.into())
         }
     }
 }
@@ -225,85 +447,1094 @@ async fn update_user(
     delete,
     path = "/users/{id}",
     tag = "Users",
+    security(("bearerAuth" = []), ("apiKeyAuth" = [])),
     responses(
-        (status = 200, description = "User deleted"),
+        (status = 200, description = "User marked as deleted; it can be restored until the retention period ends"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Insufficient permissions"),
         (status = 404, description = "User not found"),
-        (status = 500, description = "Internal server error")
+        (status = 429, description = "Too many requests",
+            headers(("Retry-After" = u64, description = "Segundos hasta poder reintentar"))),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Database unavailable")
     ),
     params(
         ("id" = i32, description = "User ID")
     )
 )]
 async fn delete_user(
-    pool: web::Data<PgPool>,
+    repo: web::Data<dyn UserRepository>,
+    auth: Authorization,
     user_id: web::Path<i32>,
-) -> Result<web::Json<OkModel<()>>, AppError> {
+) -> AppResult<()> {
     let user_id = user_id.into_inner();
-    
-    match sqlx::query("DELETE FROM users WHERE id = $1")
-        .bind(user_id)
-        .execute(pool.get_ref())
-        .await
-    {
-        Ok(result) if result.rows_affected() > 0 => {
+    auth.require(Permission::DeleteUser)?;
+    match repo.get(user_id).await {
+        Ok(user) if user.role == Role::Admin => auth.require(Permission::ModifyAdmin)?,
+        Ok(_) | Err(RepoError::NotFound) => {}
+        Err(e) => {
+            log::error!("Error al obtener usuario {}: {}", user_id, e);
+            return Err(e.into());
+        }
+    }
+
+    match repo.delete(user_id, auth.actor()).await {
+        Ok(()) => {
+            log::info!("Usuario {} eliminado por {}", user_id, auth.subject());
             Ok(web::Json(OkModel {
                 success: true,
                 data: (),
+                pagination: None,
             }))
         },
-        Ok(_) => {
-            // No rows affected - user didn't exist
-            Err(AppError::Invalid {
-                err: "Usuario no encontrado",
-            })
+        Err(RepoError::NotFound) => {
+            // No rows affected - user didn't exist or was already deleted
+            Err(user_not_found(user_id))
         },
         Err(e) => {
             log::error!("Error al eliminar usuario {}: {}", user_id, e);
-            Err(AppError::InternalError)
+            Err(e.into())
         }
     }
 }
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    let pool = db::get_db_pool().await.unwrap();
-    
-    // Initialize OpenAPI documentation
-    let openapi = OPENAPI.get_or_init(|| ApiDoc::openapi());
+// Recuperar un usuario eliminado
+#[utoipa::path(
+    post,
+    path = "/users/{id}/restore",
+    tag = "Users",
+    security(("bearerAuth" = []), ("apiKeyAuth" = [])),
+    responses(
+        (status = 200, body = User, description = "User restored (or it was not deleted)"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "User not found or already purged"),
+        (status = 409, description = "Email registered by another user after the deletion"),
+        (status = 429, description = "Too many requests",
+            headers(("Retry-After" = u64, description = "Segundos hasta poder reintentar"))),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Database unavailable")
+    ),
+    params(
+        ("id" = i32, description = "User ID")
+    )
+)]
+async fn restore_user(
+    repo: web::Data<dyn UserRepository>,
+    auth: Authorization,
+    user_id: web::Path<i32>,
+) -> AppResult<User> {
+    let user_id = user_id.into_inner();
+    auth.require(Permission::RestoreUser)?;
 
-    // Asigna el HttpServer a la variable server
-    let server = HttpServer::new(move || {
-        App::new()
-            .app_data(web::Data::new(pool.clone()))
+    match repo.restore(user_id, auth.actor()).await {
+        Ok(user) => {
+            log::info!("Usuario {} recuperado por {}", user_id, auth.subject());
+            Ok(web::Json(OkModel {
+                success: true,
+                data: user,
+                pagination: None,
+            }))
+        },
+        Err(RepoError::NotFound) => Err(user_not_found(user_id)),
+        Err(RepoError::Duplicate) => {
+            // Otro usuario registró el mismo email mientras estaba eliminado
+            match repo.get_any(user_id).await {
+                Ok(user) => Err(email_taken(&user.email)),
+                // Purgado entre la recuperación y la consulta
+                Err(RepoError::NotFound) => Err(user_not_found(user_id)),
+                Err(e) => {
+                    log::error!("Error al recuperar usuario {}: {}", user_id, e);
+                    Err(e.into())
+                }
+            }
+        },
+        Err(e) => {
+            log::error!("Error al recuperar usuario {}: {}", user_id, e);
+            Err(e.into())
+        }
+    }
+}
+
+// Iniciar sesión con email y contraseña
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    tag = "Auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, body = TokenResponse, description = "Access and refresh tokens"),
+        (status = 400, description = "Bad request"),
+        (status = 401, description = "Invalid email or password"),
+        (status = 429, description = "Too many requests",
+            headers(("Retry-After" = u64, description = "Segundos hasta poder reintentar"))),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Database unavailable")
+    )
+)]
+async fn login(
+    repo: web::Data<dyn UserRepository>,
+    tokens: web::Data<dyn TokenRepository>,
+    issuer: web::Data<TokenIssuer>,
+    credentials: web::Json<LoginRequest>,
+) -> Result<HttpResponse, AppError> {
+    let LoginRequest { mut email, password } = credentials.into_inner();
+    validation::normalize_email(&mut email);
+
+    let stored = match repo.credentials(&email).await {
+        Ok(stored) => Some(stored),
+        Err(RepoError::NotFound) => None,
+        Err(e) => {
+            log::error!("Error al obtener credenciales: {}", e);
+            return Err(e.into());
+        }
+    };
+
+    // Se verifica siempre, exista o no el usuario, para no revelar qué emails están registrados
+    let (user_id, hash) = match stored {
+        Some(stored) => (Some(stored.user.id), stored.password_hash),
+        None => (None, None),
+    };
+    let user_id = match (password::verify(password, hash).await, user_id) {
+        (true, Some(user_id)) => user_id,
+        _ => return Err(invalid_credentials()),
+    };
+
+    let response = issuer.issue(tokens.get_ref(), user_id, None).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// Renovar el access token con un refresh token
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    tag = "Auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, body = TokenResponse, description = "New access and refresh tokens"),
+        (status = 400, description = "Bad request"),
+        (status = 401, description = "Invalid, expired or reused refresh token"),
+        (status = 429, description = "Too many requests",
+            headers(("Retry-After" = u64, description = "Segundos hasta poder reintentar"))),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Database unavailable")
+    )
+)]
+async fn refresh(
+    repo: web::Data<dyn UserRepository>,
+    tokens: web::Data<dyn TokenRepository>,
+    issuer: web::Data<TokenIssuer>,
+    body: web::Json<RefreshRequest>,
+) -> Result<HttpResponse, AppError> {
+    // Cada refresh token se usa una sola vez: consumirlo lo revoca
+    let stored = match tokens.consume(&session::hash_token(&body.refresh_token)).await {
+        Ok(Consumed::Valid(stored)) => stored,
+        Ok(Consumed::Reused(stored)) => {
+            // Un token ya rotado que vuelve a usarse indica robo: se cierra toda la sesión
+            log::warn!("Refresh token reutilizado del usuario {}: se revoca la sesión", stored.user_id);
+            tokens.revoke_family(&stored.family).await?;
+            return Err(invalid_refresh_token());
+        }
+        Err(RepoError::NotFound) => return Err(invalid_refresh_token()),
+        Err(e) => {
+            log::error!("Error al consumir refresh token: {}", e);
+            return Err(e.into());
+        }
+    };
+
+    if stored.expires_at <= Utc::now() {
+        return Err(invalid_refresh_token());
+    }
+    // El usuario pudo eliminarse después de iniciar sesión
+    match repo.get(stored.user_id).await {
+        Ok(_) => {}
+        Err(RepoError::NotFound) => return Err(invalid_refresh_token()),
+        Err(e) => {
+            log::error!("Error al obtener usuario {}: {}", stored.user_id, e);
+            return Err(e.into());
+        }
+    }
+
+    let response = issuer.issue(tokens.get_ref(), stored.user_id, Some(stored.family)).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// Cerrar sesión revocando el refresh token
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    tag = "Auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 204, description = "Session closed"),
+        (status = 400, description = "Bad request"),
+        (status = 429, description = "Too many requests",
+            headers(("Retry-After" = u64, description = "Segundos hasta poder reintentar"))),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Database unavailable")
+    )
+)]
+async fn logout(
+    tokens: web::Data<dyn TokenRepository>,
+    body: web::Json<RefreshRequest>,
+) -> Result<HttpResponse, AppError> {
+    // Un token desconocido o ya revocado no es un error: la sesión ya está cerrada
+    match tokens.consume(&session::hash_token(&body.refresh_token)).await {
+        Ok(Consumed::Valid(stored) | Consumed::Reused(stored)) => tokens.revoke_family(&stored.family).await?,
+        Err(RepoError::NotFound) => {}
+        Err(e) => {
+            log::error!("Error al revocar refresh token: {}", e);
+            return Err(e.into());
+        }
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Listar las API keys
+#[utoipa::path(
+    get,
+    path = "/api-keys",
+    tag = "API keys",
+    security(("bearerAuth" = [])),
+    responses(
+        (status = 200, body = ApiKeyList, description = "All API keys, including revoked ones"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 429, description = "Too many requests",
+            headers(("Retry-After" = u64, description = "Segundos hasta poder reintentar"))),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Database unavailable")
+    )
+)]
+async fn list_api_keys(
+    keys: web::Data<dyn ApiKeyRepository>,
+    auth: Authorization,
+) -> AppResult<Vec<ApiKey>> {
+    auth.require(Permission::ManageApiKeys)?;
+
+    let api_keys = keys.list().await.map_err(|e| {
+        log::error!("Error al listar API keys: {}", e);
+        AppError::from(e)
+    })?;
+
+    Ok(web::Json(OkModel {
+        success: true,
+        data: api_keys,
+        pagination: None,
+    }))
+}
+
+// Crear una API key
+#[utoipa::path(
+    post,
+    path = "/api-keys",
+    tag = "API keys",
+    security(("bearerAuth" = [])),
+    request_body = CreateApiKey,
+    responses(
+        (status = 201, body = CreatedApiKey, description = "API key created; `key` is only returned once"),
+        (status = 400, description = "Bad request"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 429, description = "Too many requests",
+            headers(("Retry-After" = u64, description = "Segundos hasta poder reintentar"))),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Database unavailable")
+    )
+)]
+async fn create_api_key(
+    keys: web::Data<dyn ApiKeyRepository>,
+    auth: Authorization,
+    new_key: web::Json<CreateApiKey>,
+) -> Result<HttpResponse, AppError> {
+    auth.require(Permission::ManageApiKeys)?;
+    let new_key = validation::validate(new_key.into_inner())?;
+
+    let (key, prefix) = auth::generate_api_key();
+    let api_key = keys
+        .create(&NewApiKey {
+            name: &new_key.name,
+            prefix: &prefix,
+            key_hash: &session::hash_token(&key),
+            scopes: &new_key.scopes,
+            expires_at: new_key.expires_at,
+            created_by: auth.user_id(),
+        })
+        .await
+        .map_err(|e| {
+            log::error!("Error al crear API key: {}", e);
+            AppError::from(e)
+        })?;
+    log::info!("API key {} ({}) creada por {}", api_key.id, api_key.name, auth.subject());
+
+    Ok(HttpResponse::Created().json(OkModel {
+        success: true,
+        data: CreatedApiKey { key, api_key },
+        pagination: None,
+    }))
+}
+
+// Revocar una API key
+#[utoipa::path(
+    delete,
+    path = "/api-keys/{id}",
+    tag = "API keys",
+    security(("bearerAuth" = [])),
+    responses(
+        (status = 204, description = "API key revoked"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "API key not found or already revoked"),
+        (status = 429, description = "Too many requests",
+            headers(("Retry-After" = u64, description = "Segundos hasta poder reintentar"))),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Database unavailable")
+    ),
+    params(
+        ("id" = i32, description = "API key ID")
+    )
+)]
+async fn revoke_api_key(
+    keys: web::Data<dyn ApiKeyRepository>,
+    auth: Authorization,
+    key_id: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let key_id = key_id.into_inner();
+    auth.require(Permission::ManageApiKeys)?;
+
+    match keys.revoke(key_id).await {
+        Ok(()) => {
+            log::info!("API key {} revocada por {}", key_id, auth.subject());
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Err(RepoError::NotFound) => Err(AppError::not_found(
+            ErrorCode::ApiKeyNotFound,
+            format!("API key {} no encontrada", key_id),
+        )
+        .with_details(json!({ "id": key_id }))),
+        Err(e) => {
+            log::error!("Error al revocar API key {}: {}", key_id, e);
+            Err(e.into())
+        }
+    }
+}
+
+// Informe detallado de cada dependencia
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "Health",
+    responses(
+        (status = 200, body = HealthReport, description = "Every dependency is up, with its latency"),
+        (status = 503, body = HealthReport, description = "Some dependency is down")
+    )
+)]
+async fn health(health: web::Data<Health>, shutdown: web::Data<Shutdown>) -> HttpResponse {
+    health_response(health.report(&shutdown).await)
+}
+
+// Comprobar que el proceso responde
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "Health",
+    responses(
+        (status = 200, body = HealthReport, description = "The process is running")
+    )
+)]
+async fn liveness() -> HttpResponse {
+    health_response(HealthReport::alive())
+}
+
+// Comprobar que la instancia puede atender peticiones
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "Health",
+    responses(
+        (status = 200, body = HealthReport, description = "Database reachable and migrations applied"),
+        (status = 503, body = HealthReport, description = "Shutting down, database unreachable or migrations pending")
+    )
+)]
+async fn readiness(health: web::Data<Health>, shutdown: web::Data<Shutdown>) -> HttpResponse {
+    let report = health.report(&shutdown).await;
+    if !report.is_up() {
+        log::debug!("Instancia no lista: {:?}", report.checks);
+    }
+    health_response(report.summary())
+}
+
+// Métricas en el formato de texto de Prometheus
+async fn export_metrics(metrics: web::Data<Metrics>) -> HttpResponse {
+    match metrics.render() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4; charset=utf-8")
+            .insert_header((CACHE_CONTROL, "no-store"))
+            .body(body),
+        Err(e) => {
+            log::error!("Error al exportar las métricas: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+fn health_response(report: HealthReport) -> HttpResponse {
+    let mut response = if report.is_up() { HttpResponse::Ok() } else { HttpResponse::ServiceUnavailable() };
+    response.insert_header((CACHE_CONTROL, "no-store")).json(report)
+}
+
+fn invalid_credentials() -> AppError {
+    AppError::Unauthorized {
+        code: ErrorCode::InvalidCredentials,
+        err: "Email o contraseña incorrectos".into(),
+        details: None,
+    }
+}
+
+fn invalid_refresh_token() -> AppError {
+    AppError::Unauthorized {
+        code: ErrorCode::InvalidRefreshToken,
+        err: "Refresh token inválido o expirado".into(),
+        details: None,
+    }
+}
+
+fn user_not_found(id: i32) -> AppError {
+    AppError::not_found(ErrorCode::UserNotFound, format!("Usuario {} no encontrado", id))
+        .with_details(json!({ "id": id }))
+}
+
+fn email_taken(email: &str) -> AppError {
+    AppError::conflict(ErrorCode::EmailTaken, format!("El email {} ya está registrado", email))
+        .with_details(json!({ "email": email }))
+}
+
+/// Rutas de `/users` y `/api-keys`, que exigen credenciales.
+fn api_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/users")
+            .wrap(middleware::from_fn(auth::require_auth))
             .service(
-                web::resource("/users")
+                web::resource("")
                     .route(web::get().to(get_users))
                     .route(web::post().to(create_user)),
             )
             .service(
-                web::resource("/users/{id}")
+                web::resource("/{id}")
                     .route(web::get().to(get_user))
                     .route(web::put().to(update_user))
+                    .route(web::patch().to(patch_user))
                     .route(web::delete().to(delete_user)),
             )
+            .service(web::resource("/{id}/restore").route(web::post().to(restore_user))),
+    )
+    .service(
+        web::scope("/api-keys")
+            .wrap(middleware::from_fn(auth::require_auth))
+            .service(
+                web::resource("")
+                    .route(web::get().to(list_api_keys))
+                    .route(web::post().to(create_api_key)),
+            )
+            .service(web::resource("/{id}").route(web::delete().to(revoke_api_key))),
+    );
+}
+
+/// Rutas de `/auth`; necesitan un `TokenIssuer` registrado en el scope.
+fn auth_routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/login", web::post().to(login))
+        .route("/refresh", web::post().to(refresh))
+        .route("/logout", web::post().to(logout));
+}
+
+/// Ejecuta los subcomandos de mantenimiento del esquema y termina.
+async fn migration_command(command: Command, pool: &PgPool) -> io::Result<()> {
+    match command {
+        Command::Migrate => {
+            migrations::run(pool).await.map_err(io::Error::other)?;
+            println!("Migraciones aplicadas correctamente");
+        }
+        Command::Rollback { steps } => {
+            let reverted = migrations::rollback(pool, steps).await.map_err(io::Error::other)?;
+            if reverted.is_empty() {
+                println!("No hay migraciones aplicadas que revertir");
+            }
+            for version in reverted {
+                println!("Migración {} revertida", version);
+            }
+        }
+        Command::Status => {
+            for migration in migrations::status(pool).await.map_err(io::Error::other)? {
+                let estado = if migration.applied { "aplicada" } else { "pendiente" };
+                println!("{} {:<10} {}", migration.version, estado, migration.description);
+            }
+        }
+        Command::Serve { .. } => unreachable!("serve no es un subcomando de migración"),
+    }
+
+    Ok(())
+}
+
+#[actix_web::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // Mensaje legible en lugar del `Debug` que imprime un `main` que devuelve `Result`
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> io::Result<()> {
+    dotenv::dotenv().ok();
+    let Cli { command, config, overrides } = Cli::parse(env::args().skip(1))?;
+    let config = Config::load(config.as_deref(), &overrides)?;
+    logging::init(&config);
+    let tracer_provider = telemetry::init(&config.tracing)?;
+
+    let demo = matches!(command, Command::Serve { demo: true });
+    // Sin pool (modo demo) todo se guarda en memoria
+    let pool = match command {
+        Command::Serve { demo: true } => {
+            log::info!("Modo demo: los usuarios se guardan en memoria y se pierden al salir");
+            None
+        }
+        Command::Serve { demo: false } => {
+            let pool = db::get_db_pool(&config.database).await?;
+            // Deja el esquema al día antes de aceptar peticiones
+            migrations::run(&pool).await.map_err(io::Error::other)?;
+            Some(pool)
+        }
+        command => {
+            let pool = db::get_db_pool(&config.database).await?;
+            return migration_command(command, &pool).await;
+        }
+    };
+
+    let (repo, tokens, api_keys): (Arc<dyn UserRepository>, Arc<dyn TokenRepository>, Arc<dyn ApiKeyRepository>) =
+        match &pool {
+            Some(pool) => (
+                Arc::new(PgUserRepository::new(pool.clone())),
+                Arc::new(PgTokenRepository::new(pool.clone())),
+                Arc::new(PgApiKeyRepository::new(pool.clone())),
+            ),
+            None => (
+                Arc::new(InMemoryUserRepository::default()),
+                Arc::new(InMemoryTokenRepository::default()),
+                Arc::new(InMemoryApiKeyRepository::default()),
+            ),
+        };
+    // Cada operación de los repositorios que usan los handlers queda medida en `/metrics`
+    let metrics = Arc::new(Metrics::new(pool.clone()).map_err(io::Error::other)?);
+    let repo: Arc<dyn UserRepository> = Arc::new(Instrumented::new(repo, "users", metrics.clone()));
+    let tokens: Arc<dyn TokenRepository> = Arc::new(Instrumented::new(tokens, "refresh_tokens", metrics.clone()));
+    let api_keys: Arc<dyn ApiKeyRepository> = Arc::new(Instrumented::new(api_keys, "api_keys", metrics.clone()));
+    // Con varias instancias, `rate_limit.store = "postgres"` comparte los límites entre todas
+    let rate_limits: Arc<dyn RateLimitStore> = match &pool {
+        Some(pool) if config.rate_limit.store == RateLimitStoreKind::Postgres => {
+            Arc::new(PgRateLimitStore::new(pool.clone()))
+        }
+        _ => Arc::new(InMemoryRateLimitStore::default()),
+    };
+
+    permissions::bootstrap_admin(repo.as_ref()).await?;
+
+    // Initialize OpenAPI documentation
+    let swagger = config.swagger_enabled().then(|| {
+        let openapi = OPENAPI.get_or_init(|| docs::openapi(ApiDoc::openapi(), &config.server.base_path));
+        docs::swagger_ui(&config, openapi.clone())
+    });
+    let docs_access = web::Data::new(docs::DocsAccess::from_config(&config));
+    let health_checks = web::Data::new(Health::new(pool.clone(), config.health.timeout()));
+    let metrics_path = web::Data::new(MetricsPath(config.metrics.path.clone()));
+    let metrics_enabled = config.metrics.enabled;
+    let problem_config = ProblemConfig::from_config(&config.errors);
+    let base_path = web::Data::new(BasePath(config.server.base_path.clone()));
+    let jwt_verifier = match JwtVerifier::from_config(&config.jwt)? {
+        Some(verifier) => Some(web::Data::new(verifier)),
+        // La demo se puede probar sin claves, pero nadie podría autenticarse en /users ni /api-keys
+        None if demo => {
+            log::warn!(
+                "Sin jwt.secret, jwt.public_key ni jwt.jwks no se verifican tokens: /users y /api-keys \
+                 quedan deshabilitados"
+            );
+            None
+        }
+        None => {
+            return Err(io::Error::other(
+                "No hay claves para verificar tokens: defina jwt.secret, jwt.public_key o jwt.jwks",
+            ));
+        }
+    };
+    let token_issuer = TokenIssuer::from_config(&config.jwt)?.map(web::Data::new);
+    let rate_limiter = web::Data::new(RateLimiter::new(&config.rate_limit, rate_limits));
+    let shutdown = Shutdown::new();
+    let shutdown_data = web::Data::from(shutdown.clone());
+    rate_limiter.spawn_purge(&shutdown);
+    retention::spawn_purge(&shutdown, repo.clone(), &config.users);
+    if token_issuer.is_none() {
+        log::warn!("Sin jwt.secret ni jwt.private_key no se emiten tokens: /auth queda deshabilitado");
+    }
+
+    // Asigna el HttpServer a la variable server
+    let mut server = HttpServer::new(move || {
+        App::new()
+            .wrap(middleware::from_fn(ratelimit::limit))
+            .wrap(middleware::from_fn(docs::require_auth))
+            .wrap(middleware::from_fn(problem::render_errors))
+            .wrap(middleware::from_fn(shutdown::close_when_draining))
+            .wrap(middleware::from_fn(metrics::track))
+            .wrap(middleware::from_fn(logging::access_log))
+            .wrap(middleware::from_fn(telemetry::trace_requests))
+            .wrap(middleware::from_fn(request_id::assign))
+            .app_data(web::Data::from(repo.clone()))
+            .app_data(web::Data::from(tokens.clone()))
+            .app_data(web::Data::from(api_keys.clone()))
+            .app_data(web::Data::new(problem_config))
+            .app_data(base_path.clone())
+            .app_data(rate_limiter.clone())
+            .app_data(docs_access.clone())
+            .app_data(shutdown_data.clone())
+            .app_data(health_checks.clone())
+            .app_data(web::Data::from(metrics.clone()))
+            .app_data(metrics_path.clone())
+            .app_data(web::QueryConfig::default().error_handler(|err, _| {
+                AppError::invalid(ErrorCode::InvalidQuery, format!("Parámetros de consulta inválidos: {}", err))
+                    .with_details(json!({ "reason": err.to_string() }))
+                    .into()
+            }))
+            .app_data(web::JsonConfig::default().error_handler(|err, _| {
+                AppError::invalid(ErrorCode::InvalidBody, format!("Cuerpo JSON inválido: {}", err))
+                    .with_details(json!({ "reason": err.to_string() }))
+                    .into()
+            }))
             .service(
-                SwaggerUi::new("/swagger-ui/{_:.*}")
-                    .url("/api-docs/openapi.json", openapi.clone()),
+                web::scope("/health")
+                    .route("", web::get().to(health))
+                    .route("/live", web::get().to(liveness))
+                    .route("/ready", web::get().to(readiness)),
             )
-    })
-    .bind("127.0.0.1:8080")?;
+            .configure(|cfg| {
+                if metrics_enabled {
+                    cfg.route(&metrics_path.0, web::get().to(export_metrics));
+                }
+            })
+            .configure(|cfg| {
+                if let Some(verifier) = &jwt_verifier {
+                    cfg.app_data(verifier.clone()).configure(api_routes);
+                }
+            })
+            .configure(|cfg| {
+                // Sin clave de firma solo se aceptan tokens de un emisor externo
+                if let Some(issuer) = &token_issuer {
+                    cfg.service(web::scope("/auth").app_data(issuer.clone()).configure(auth_routes));
+                }
+            })
+            .configure(|cfg| {
+                if let Some(swagger) = &swagger {
+                    cfg.service(swagger.clone());
+                }
+            })
+    });
+    if let Some(workers) = config.server.workers {
+        server = server.workers(workers);
+    }
+    // Las señales las atiende `Shutdown` para marcar el servicio como no listo antes de parar
+    server = server.disable_signals().shutdown_timeout(config.server.shutdown_timeout_secs);
+    let (host, port) = config.bind_address();
+    let server = server.bind((host, port)).map_err(|e| {
+        io::Error::new(e.kind(), format!("No se pudo escuchar en {}:{}: {}", host, port, e))
+    })?;
 
-    // URL de Swagger UI
-    let swagger_url = "http://localhost:8080/swagger-ui/";
+    // En 0.0.0.0 se escucha en todas las interfaces; el navegador necesita una concreta
+    let public_host = if host == "0.0.0.0" { "localhost" } else { host };
+    log::info!("Servidor iniciado en http://{}:{}/", public_host, port);
 
-    println!("Servidor iniciado en {}", swagger_url);
+    if config.swagger_enabled() {
+        // URL de Swagger UI; el navegador se abre en esta máquina, sin pasar por el proxy
+        let swagger_url = format!("http://{}:{}{}/", public_host, port, config.swagger.path);
 
-    // Intenta abrir el navegador
-    if webbrowser::open(swagger_url).is_err() {
-        println!("No se pudo abrir el navegador automáticamente. Por favor visita: {}", swagger_url);
+        // Intenta abrir el navegador
+        if !config.open_browser() {
+            log::info!("Documentación en {}", swagger_url);
+        } else if webbrowser::open(&swagger_url).is_err() {
+            log::warn!("No se pudo abrir el navegador automáticamente. Por favor visita: {}", swagger_url);
+        }
     }
 
     // Inicia el servidor
-    server.run().await
+    let server = server.run();
+    actix_web::rt::spawn(shutdown.clone().on_signal(server.handle(), config.server.drain_delay()));
+    server.await?;
+
+    // Ya no quedan peticiones: se paran los workers y después se cierra el pool que usan
+    shutdown.stop_workers(config.server.shutdown_timeout()).await;
+    if let Some(pool) = pool {
+        pool.close().await;
+    }
+    if let Some(provider) = tracer_provider {
+        telemetry::shutdown(provider).await;
+    }
+    log::info!("Servidor detenido");
+    Ok(())
+
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        body::MessageBody,
+        dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+        http::{header::{HeaderName, AUTHORIZATION, CONTENT_TYPE}, StatusCode},
+        test::{self, TestRequest},
+    };
+    use serde_json::Value;
+
+    use super::*;
+
+    const SECRET: &str = "secreto-de-pruebas";
+
+    /// Repositorios en memoria que comparten la aplicación y las pruebas.
+    struct Fixture {
+        repo: Arc<dyn UserRepository>,
+        tokens: Arc<dyn TokenRepository>,
+        api_keys: Arc<dyn ApiKeyRepository>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            Self {
+                repo: Arc::new(InMemoryUserRepository::default()),
+                tokens: Arc::new(InMemoryTokenRepository::default()),
+                api_keys: Arc::new(InMemoryApiKeyRepository::default()),
+            }
+        }
+
+        /// Aplicación con las mismas rutas que `run`, sin los middlewares transversales.
+        fn app(
+            &self,
+        ) -> App<
+            impl ServiceFactory<
+                ServiceRequest,
+                Config = (),
+                Response = ServiceResponse<impl MessageBody + use<>>,
+                Error = actix_web::Error,
+                InitError = (),
+            > + use<>,
+        > {
+            App::new()
+                .app_data(web::Data::from(self.repo.clone()))
+                .app_data(web::Data::from(self.tokens.clone()))
+                .app_data(web::Data::from(self.api_keys.clone()))
+                .app_data(web::Data::new(JwtVerifier::from_secret(SECRET)))
+                .configure(api_routes)
+                .service(
+                    web::scope("/auth")
+                        .app_data(web::Data::new(TokenIssuer::from_secret(SECRET)))
+                        .configure(auth_routes),
+                )
+        }
+
+        async fn user(&self, email: &str, role: Role, password: Option<&str>) -> User {
+            let user = CreateUser { name: "Prueba".into(), email: email.into(), password: None };
+            let hash = password::hash_optional(password).await.unwrap();
+            let user = self.repo.create(&user, hash.as_deref(), repository::Actor::System).await.unwrap();
+            self.repo.set_role(user.id, role).await.unwrap()
+        }
+
+        /// Cabecera `Authorization` con un access token de `user_id`.
+        async fn bearer(&self, user_id: i32) -> (HeaderName, String) {
+            let issued = TokenIssuer::from_secret(SECRET).issue(self.tokens.as_ref(), user_id, None).await.unwrap();
+            (AUTHORIZATION, format!("Bearer {}", issued.access_token))
+        }
+
+        /// Cabecera `X-API-Key` con una clave nueva con `scopes`; devuelve también su ID.
+        async fn api_key(&self, scopes: &[Scope]) -> ((HeaderName, String), i32) {
+            let (key, prefix) = auth::generate_api_key();
+            let key_hash = session::hash_token(&key);
+            let new_key =
+                NewApiKey { name: "pruebas", prefix: &prefix, key_hash: &key_hash, scopes, expires_at: None, created_by: None };
+            let created = self.api_keys.create(&new_key).await.unwrap();
+            ((HeaderName::from_static(auth::API_KEY_HEADER), key), created.id)
+        }
+    }
+
+    async fn json<B: MessageBody>(res: ServiceResponse<B>) -> Value {
+        serde_json::from_slice(&test::read_body(res).await).unwrap_or(Value::Null)
+    }
+
+    #[actix_web::test]
+    async fn crud_en_memoria() {
+        let fixture = Fixture::new();
+        let admin = fixture.user("admin@example.com", Role::Admin, None).await;
+        let auth = fixture.bearer(admin.id).await;
+        let app = test::init_service(fixture.app()).await;
+
+        let req = TestRequest::post()
+            .uri("/users")
+            .insert_header(auth.clone())
+            .set_json(json!({ "name": "Ana", "email": "ana@example.com" }));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let id = json(res).await["data"]["id"].as_i64().unwrap();
+        let uri = format!("/users/{}", id);
+
+        let req = TestRequest::put()
+            .uri(&uri)
+            .insert_header(auth.clone())
+            .set_json(json!({ "name": "Ana María", "email": "ana@example.com" }));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(json(res).await["data"]["name"], "Ana María");
+
+        let res = test::call_service(&app, TestRequest::get().uri(&uri).insert_header(auth.clone()).to_request()).await;
+        assert_eq!(json(res).await["data"]["name"], "Ana María");
+
+        let res = test::call_service(&app, TestRequest::delete().uri(&uri).insert_header(auth.clone()).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = test::call_service(&app, TestRequest::get().uri(&uri).insert_header(auth).to_request()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn sin_credenciales_responde_401() {
+        let fixture = Fixture::new();
+        let app = test::init_service(fixture.app()).await;
 
+        let res = test::call_service(&app, TestRequest::get().uri("/users").to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let req = TestRequest::get().uri("/users").insert_header((AUTHORIZATION, "Bearer no-es-un-jwt"));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(json(res).await["code"], "INVALID_TOKEN");
+    }
+
+    #[actix_web::test]
+    async fn admin_crea_y_pagina_usuarios() {
+        let fixture = Fixture::new();
+        let admin = fixture.user("admin@example.com", Role::Admin, None).await;
+        let auth = fixture.bearer(admin.id).await;
+        let app = test::init_service(fixture.app()).await;
+
+        for email in ["ana@example.com", "bea@example.com", "carla@example.com"] {
+            let req = TestRequest::post()
+                .uri("/users")
+                .insert_header(auth.clone())
+                .set_json(json!({ "name": "Nueva", "email": email }));
+            let res = test::call_service(&app, req.to_request()).await;
+            assert_eq!(res.status(), StatusCode::CREATED);
+            assert_eq!(json(res).await["data"]["created_by"], admin.id);
+        }
+
+        let req = TestRequest::post()
+            .uri("/users")
+            .insert_header(auth.clone())
+            .set_json(json!({ "name": "Otra", "email": "ana@example.com" }));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+
+        let req = TestRequest::get().uri("/users?limit=2&sort=-email").insert_header(auth.clone());
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let link = res.headers().get(LINK).unwrap().to_str().unwrap().to_string();
+        let body = json(res).await;
+        assert_eq!(body["pagination"]["total"], 4);
+        assert_eq!(body["data"][0]["email"], "carla@example.com");
+        assert_eq!(body["data"][1]["email"], "bea@example.com");
+        assert!(link.contains("rel=\"next\""));
+
+        // La página siguiente continúa desde el cursor
+        let cursor = body["pagination"]["next_cursor"].as_str().unwrap();
+        let req = TestRequest::get().uri(&format!("/users?limit=2&sort=-email&cursor={}", cursor)).insert_header(auth);
+        let body = json(test::call_service(&app, req.to_request()).await).await;
+        assert_eq!(body["data"][0]["email"], "ana@example.com");
+        assert_eq!(body["data"][1]["email"], "admin@example.com");
+        assert!(body["pagination"]["next_cursor"].is_null());
+    }
+
+    #[actix_web::test]
+    async fn listado_rechaza_parametros_invalidos() {
+        let fixture = Fixture::new();
+        let admin = fixture.user("admin@example.com", Role::Admin, None).await;
+        let auth = fixture.bearer(admin.id).await;
+        let app = test::init_service(fixture.app()).await;
+
+        for (uri, code) in [
+            ("/users?sort=password", "INVALID_SORT"),
+            ("/users?cursor=basura", "INVALID_CURSOR"),
+            ("/users?offset=9223372036854775808", "INVALID_QUERY"),
+        ] {
+            let res = test::call_service(&app, TestRequest::get().uri(uri).insert_header(auth.clone()).to_request()).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            assert_eq!(json(res).await["code"], code, "{}", uri);
+        }
+    }
+
+    #[actix_web::test]
+    async fn usuario_solo_accede_a_su_registro() {
+        let fixture = Fixture::new();
+        let ana = fixture.user("ana@example.com", Role::User, None).await;
+        let bea = fixture.user("bea@example.com", Role::User, None).await;
+        let auth = fixture.bearer(ana.id).await;
+        let app = test::init_service(fixture.app()).await;
+
+        let status = |uri: String| {
+            let req = TestRequest::get().uri(&uri).insert_header(auth.clone()).to_request();
+            let app = &app;
+            async move { test::call_service(app, req).await.status() }
+        };
+        assert_eq!(status("/users".into()).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(format!("/users/{}", ana.id)).await, StatusCode::OK);
+        assert_eq!(status(format!("/users/{}", bea.id)).await, StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn patch_aplica_cambios_y_protege_campos_inmutables() {
+        let fixture = Fixture::new();
+        let ana = fixture.user("ana@example.com", Role::User, None).await;
+        let auth = fixture.bearer(ana.id).await;
+        let app = test::init_service(fixture.app()).await;
+        let uri = format!("/users/{}", ana.id);
+        let patch = |body: Value| {
+            TestRequest::patch()
+                .uri(&uri)
+                .insert_header(auth.clone())
+                .insert_header((CONTENT_TYPE, patch::MERGE_PATCH))
+                .set_payload(body.to_string())
+                .to_request()
+        };
+
+        let res = test::call_service(&app, patch(json!({ "name": "Ana María" }))).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = json(res).await;
+        assert_eq!(body["data"]["name"], "Ana María");
+        assert_eq!(body["data"]["email"], "ana@example.com");
+        assert_eq!(body["data"]["updated_by"], ana.id);
+
+        // Un parche sin cambios devuelve el usuario tal cual
+        let res = test::call_service(&app, patch(json!({ "name": "Ana María" }))).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(json(res).await["data"]["name"], "Ana María");
+
+        let res = test::call_service(&app, patch(json!({ "role": "admin" }))).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(json(res).await["details"]["field"], "role");
+
+        let res = test::call_service(&app, patch(json!({ "email": "no-es-un-email" }))).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(fixture.repo.get(ana.id).await.unwrap().email, "ana@example.com");
+    }
+
+    #[actix_web::test]
+    async fn api_key_no_cambia_contrasenas_ni_administradores() {
+        let fixture = Fixture::new();
+        let admin = fixture.user("admin@example.com", Role::Admin, None).await;
+        let ana = fixture.user("ana@example.com", Role::User, None).await;
+        let (writer, writer_id) = fixture.api_key(&[Scope::UsersRead, Scope::UsersWrite]).await;
+        let (reader, _) = fixture.api_key(&[Scope::UsersRead]).await;
+        let app = test::init_service(fixture.app()).await;
+        let merge = |id: i32, body: Value| {
+            TestRequest::patch()
+                .uri(&format!("/users/{}", id))
+                .insert_header(writer.clone())
+                .insert_header((CONTENT_TYPE, patch::MERGE_PATCH))
+                .set_payload(body.to_string())
+                .to_request()
+        };
+
+        let res = test::call_service(&app, merge(ana.id, json!({ "name": "Ana María" }))).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = json(res).await;
+        assert_eq!(body["data"]["updated_by_api_key"], writer_id);
+        assert!(body["data"]["updated_by"].is_null());
+
+        let res = test::call_service(&app, merge(ana.id, json!({ "password": "Otra-Clave-123" }))).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let res = test::call_service(&app, merge(admin.id, json!({ "name": "Intruso" }))).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let req = TestRequest::delete().uri(&format!("/users/{}", admin.id)).insert_header(writer.clone());
+        assert_eq!(test::call_service(&app, req.to_request()).await.status(), StatusCode::FORBIDDEN);
+
+        // A una clave de solo lectura se le indica el scope que le falta
+        let req = TestRequest::post()
+            .uri("/users")
+            .insert_header(reader)
+            .set_json(json!({ "name": "Nueva", "email": "nueva@example.com" }));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        assert_eq!(json(res).await["details"]["scope"], "users:write");
+    }
+
+    #[actix_web::test]
+    async fn borrado_logico_y_recuperacion() {
+        let fixture = Fixture::new();
+        let admin = fixture.user("admin@example.com", Role::Admin, None).await;
+        let ana = fixture.user("ana@example.com", Role::User, None).await;
+        let auth = fixture.bearer(admin.id).await;
+        let app = test::init_service(fixture.app()).await;
+        let uri = format!("/users/{}", ana.id);
+
+        let res = test::call_service(&app, TestRequest::delete().uri(&uri).insert_header(auth.clone()).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = test::call_service(&app, TestRequest::get().uri(&uri).insert_header(auth.clone()).to_request()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let req = TestRequest::get().uri(&format!("{}?include_deleted=true", uri)).insert_header(auth.clone());
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(json(res).await["data"]["deleted_at"].is_string());
+
+        let req = TestRequest::post().uri(&format!("{}/restore", uri)).insert_header(auth.clone());
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(json(res).await["data"].get("deleted_at").is_none());
+        let res = test::call_service(&app, TestRequest::get().uri(&uri).insert_header(auth).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn refresh_reutilizado_revoca_la_sesion() {
+        let fixture = Fixture::new();
+        fixture.user("ana@example.com", Role::User, Some("Clave-Segura-123")).await;
+        let app = test::init_service(fixture.app()).await;
+        let refresh = |token: &str| {
+            TestRequest::post().uri("/auth/refresh").set_json(json!({ "refresh_token": token })).to_request()
+        };
+
+        let req = TestRequest::post()
+            .uri("/auth/login")
+            .set_json(json!({ "email": "ana@example.com", "password": "Clave-Segura-123" }));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let first = json(res).await["refresh_token"].as_str().unwrap().to_string();
+
+        let res = test::call_service(&app, refresh(&first)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let second = json(res).await["refresh_token"].as_str().unwrap().to_string();
+
+        // Reutilizar un token ya rotado cierra toda la sesión, también el token vigente
+        let res = test::call_service(&app, refresh(&first)).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(json(res).await["code"], "INVALID_REFRESH_TOKEN");
+        let res = test::call_service(&app, refresh(&second)).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn login_rechaza_credenciales_incorrectas() {
+        let fixture = Fixture::new();
+        fixture.user("ana@example.com", Role::User, Some("Clave-Segura-123")).await;
+        let app = test::init_service(fixture.app()).await;
+
+        for (email, password) in [("ana@example.com", "otra-clave-1"), ("nadie@example.com", "Clave-Segura-123")] {
+            let req = TestRequest::post().uri("/auth/login").set_json(json!({ "email": email, "password": password }));
+            let res = test::call_service(&app, req.to_request()).await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(json(res).await["code"], "INVALID_CREDENTIALS");
+        }
+    }
 }
@@ -0,0 +1,162 @@
+use std::time::{Duration, Instant};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web, Error,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use sqlx::PgPool;
+
+use crate::repository::RepoError;
+
+/// Intervalos del histograma de consultas, más finos que los de HTTP.
+const QUERY_BUCKETS: &[f64] = &[0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Ruta en la que se publican las métricas (`metrics.path`).
+#[derive(Debug, Clone)]
+pub struct MetricsPath(pub String);
+
+/// Métricas del servidor en formato Prometheus.
+///
+/// Se crea una sola instancia que comparten todos los workers de actix.
+pub struct Metrics {
+    registry: Registry,
+    /// `http_requests_total{method, route, status}`
+    http_requests: IntCounterVec,
+    /// `http_request_duration_seconds{method, route}`
+    http_duration: HistogramVec,
+    http_in_flight: IntGauge,
+    /// `db_query_duration_seconds{repository, operation, result}`
+    db_queries: HistogramVec,
+    /// `db_pool_connections{state}`: `idle` o `in_use`
+    db_pool_connections: IntGaugeVec,
+    db_pool_acquire_timeouts: IntCounter,
+    /// `None` en modo demo
+    pool: Option<PgPool>,
+}
+
+impl Metrics {
+    pub fn new(pool: Option<PgPool>) -> prometheus::Result<Self> {
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Peticiones HTTP atendidas"),
+            &["method", "route", "status"],
+        )?;
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Duración de las peticiones HTTP"),
+            &["method", "route"],
+        )?;
+        let http_in_flight = IntGauge::new("http_requests_in_flight", "Peticiones HTTP en curso")?;
+        let db_queries = HistogramVec::new(
+            HistogramOpts::new("db_query_duration_seconds", "Duración de las operaciones de los repositorios")
+                .buckets(QUERY_BUCKETS.to_vec()),
+            &["repository", "operation", "result"],
+        )?;
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Conexiones abiertas del pool por estado"),
+            &["state"],
+        )?;
+        let db_pool_max_connections =
+            IntGauge::new("db_pool_max_connections", "Máximo de conexiones del pool")?;
+        // sqlx no expone cuántas tareas esperan una conexión: la saturación se ve con
+        // `in_use` igual al máximo y con los timeouts de adquisición
+        let db_pool_acquire_timeouts = IntCounter::new(
+            "db_pool_acquire_timeouts_total",
+            "Operaciones que no obtuvieron conexión del pool a tiempo",
+        )?;
+
+        let registry = Registry::new();
+        registry.register(Box::new(http_requests.clone()))?;
+        registry.register(Box::new(http_duration.clone()))?;
+        registry.register(Box::new(http_in_flight.clone()))?;
+        registry.register(Box::new(db_queries.clone()))?;
+        registry.register(Box::new(db_pool_connections.clone()))?;
+        registry.register(Box::new(db_pool_max_connections.clone()))?;
+        registry.register(Box::new(db_pool_acquire_timeouts.clone()))?;
+
+        if let Some(pool) = &pool {
+            db_pool_max_connections.set(i64::from(pool.options().get_max_connections()));
+        }
+
+        Ok(Self {
+            registry,
+            http_requests,
+            http_duration,
+            http_in_flight,
+            db_queries,
+            db_pool_connections,
+            db_pool_acquire_timeouts,
+            pool,
+        })
+    }
+
+    /// Anota una operación de repositorio.
+    pub fn observe_query<T>(&self, repository: &str, operation: &str, elapsed: Duration, result: &Result<T, RepoError>) {
+        let outcome = match result {
+            Ok(_) => "ok",
+            Err(RepoError::NotFound) => "not_found",
+            Err(RepoError::Duplicate) => "duplicate",
+            Err(RepoError::Database(sqlx::Error::PoolTimedOut)) => {
+                self.db_pool_acquire_timeouts.inc();
+                "error"
+            }
+            Err(RepoError::Database(_)) => "error",
+        };
+        self.db_queries
+            .with_label_values(&[repository, operation, outcome])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Texto de todas las métricas; el estado del pool se lee en este momento.
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        if let Some(pool) = &self.pool {
+            let idle = pool.num_idle() as i64;
+            let size = i64::from(pool.size());
+            self.db_pool_connections.with_label_values(&["idle"]).set(idle);
+            self.db_pool_connections.with_label_values(&["in_use"]).set((size - idle).max(0));
+        }
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        String::from_utf8(buffer).map_err(|e| prometheus::Error::Msg(e.to_string()))
+    }
+}
+
+/// Middleware que cuenta y mide todas las peticiones.
+///
+/// La ruta se etiqueta con su patrón (`/users/{id}`) para no crear una serie por ID;
+/// las que no coinciden con ninguna se agrupan en `unmatched`.
+pub async fn track(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let Some(metrics) = req.app_data::<web::Data<Metrics>>().cloned() else {
+        return next.call(req).await;
+    };
+
+    let method = req.method().to_string();
+    let start = Instant::now();
+    metrics.http_in_flight.inc();
+    let res = next.call(req).await;
+    metrics.http_in_flight.dec();
+
+    // Un `Err` aquí no llega a ser respuesta; actix lo convierte después en un 500
+    let (route, status) = match &res {
+        Ok(res) => (
+            res.request().match_pattern().unwrap_or_else(|| "unmatched".to_string()),
+            res.status().as_u16().to_string(),
+        ),
+        Err(_) => ("unmatched".to_string(), "500".to_string()),
+    };
+    metrics.http_requests.with_label_values(&[&method, &route, &status]).inc();
+    metrics
+        .http_duration
+        .with_label_values(&[&method, &route])
+        .observe(start.elapsed().as_secs_f64());
+
+    res
+}
@@ -0,0 +1,63 @@
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::PgPool;
+
+/// Migraciones SQL versionadas, embebidas en el binario desde `./migrations`.
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Estado de una migración conocida por el binario.
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// Aplica todas las migraciones pendientes.
+pub async fn run(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+/// Revierte las últimas `steps` migraciones aplicadas.
+///
+/// Devuelve las versiones revertidas, de la más reciente a la más antigua.
+pub async fn rollback(pool: &PgPool, steps: usize) -> Result<Vec<i64>, MigrateError> {
+    let mut applied = applied_versions(pool).await?;
+    applied.sort_unstable_by(|a, b| b.cmp(a));
+
+    if steps == 0 || applied.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let reverted: Vec<i64> = applied.iter().take(steps).copied().collect();
+    // `undo` revierte todo lo que esté por encima de la versión objetivo
+    let target = applied.get(steps).copied().unwrap_or(0);
+    MIGRATOR.undo(pool, target).await?;
+
+    Ok(reverted)
+}
+
+/// Lista las migraciones embebidas indicando cuáles están aplicadas en la base de datos.
+pub async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>, MigrateError> {
+    let applied = applied_versions(pool).await?;
+
+    Ok(MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| MigrationStatus {
+            version: m.version,
+            description: m.description.to_string(),
+            applied: applied.contains(&m.version),
+        })
+        .collect())
+}
+
+async fn applied_versions(pool: &PgPool) -> Result<Vec<i64>, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    Ok(conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect())
+}
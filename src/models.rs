@@ -1,27 +1,336 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
-#[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)] 
+use crate::password;
+use crate::query::{SortField, SortValue};
+use crate::validation::{self, Normalize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct User {
     pub id: i32,
     pub name: String,
     pub email: String,
+    /// Solo se asigna al arrancar con `ADMIN_EMAIL`; no se puede cambiar desde la API
+    pub role: Role,
+    /// Fecha de alta; este campo y los siguientes los mantiene el servidor
+    #[schema(read_only)]
+    pub created_at: DateTime<Utc>,
+    /// Último cambio, incluidos el borrado y la recuperación
+    #[schema(read_only)]
+    pub updated_at: DateTime<Utc>,
+    /// Usuario que lo creó; `null` si lo creó una API key o el servidor al arrancar
+    #[schema(read_only)]
+    pub created_by: Option<i32>,
+    /// Usuario que hizo el último cambio; `null` si fue una API key o el servidor
+    #[schema(read_only)]
+    pub updated_by: Option<i32>,
+    /// API key con la que se creó, si no lo creó un usuario
+    #[schema(read_only)]
+    pub created_by_api_key: Option<i32>,
+    /// API key con la que se hizo el último cambio, si no lo hizo un usuario
+    #[schema(read_only)]
+    pub updated_by_api_key: Option<i32>,
+    /// Fecha del borrado lógico; solo aparece en usuarios eliminados (`include_deleted=true`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(read_only)]
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+/// Rol de un usuario, guardado en la columna `users.role`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "user_role", rename_all = "lowercase")]
+pub enum Role {
+    /// Solo puede leer y modificar su propio registro
+    #[default]
+    User,
+    /// Puede listar, crear, modificar y eliminar cualquier usuario
+    Admin,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct CreateUser {
+    #[validate(
+        length(min = 1, max = 255),
+        custom(function = validation::no_control_chars)
+    )]
+    #[schema(min_length = 1, max_length = 255, example = "Ana García")]
     pub name: String,
+    /// Email con sintaxis RFC 5322; el dominio se guarda en minúsculas
+    #[validate(
+        length(min = 1, max = 255),
+        custom(function = validation::email)
+    )]
+    #[schema(min_length = 1, max_length = 255, format = "email", example = "ana@example.com")]
     pub email: String,
+    /// Contraseña para `/auth/login`; si se omite al actualizar se conserva la actual
+    #[serde(default)]
+    #[validate(length(min = 8, max = 128), custom(function = password::strength))]
+    #[schema(min_length = 8, max_length = 128, format = Password, write_only)]
+    pub password: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct UpdateUser {
+    #[validate(
+        length(min = 1, max = 255),
+        custom(function = validation::no_control_chars)
+    )]
+    #[schema(min_length = 1, max_length = 255, example = "Ana García")]
     pub name: Option<String>,
+    /// Email con sintaxis RFC 5322; el dominio se guarda en minúsculas
+    #[validate(
+        length(min = 1, max = 255),
+        custom(function = validation::email)
+    )]
+    #[schema(min_length = 1, max_length = 255, format = "email", example = "ana@example.com")]
     pub email: Option<String>,
+    #[serde(default)]
+    #[validate(length(min = 8, max = 128), custom(function = password::strength))]
+    #[schema(min_length = 8, max_length = 128, format = Password, write_only)]
+    pub password: Option<String>,
+}
+
+impl Normalize for CreateUser {
+    fn normalize(&mut self) {
+        validation::trim(&mut self.name);
+        validation::normalize_email(&mut self.email);
+    }
+}
+
+impl Normalize for UpdateUser {
+    fn normalize(&mut self) {
+        if let Some(name) = &mut self.name {
+            validation::trim(name);
+        }
+        if let Some(email) = &mut self.email {
+            validation::normalize_email(email);
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct DeleteUser {
     pub id: i32,
-}
\ No newline at end of file
+}
+
+/// Credenciales de `POST /auth/login`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LoginRequest {
+    #[schema(example = "ana@example.com")]
+    pub email: String,
+    #[schema(format = Password)]
+    pub password: String,
+}
+
+/// Cuerpo de `POST /auth/refresh` y `POST /auth/logout`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Tokens emitidos por `/auth/login` y `/auth/refresh`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TokenResponse {
+    /// JWT para la cabecera `Authorization: Bearer`
+    pub access_token: String,
+    #[schema(example = "Bearer")]
+    pub token_type: &'static str,
+    /// Segundos de validez del `access_token`
+    #[schema(example = 900)]
+    pub expires_in: i64,
+    /// Token opaco de un solo uso para pedir un nuevo par de tokens
+    pub refresh_token: String,
+}
+
+/// Permiso concedido a una API key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+pub enum Scope {
+    /// Listar y leer usuarios
+    #[serde(rename = "users:read")]
+    UsersRead,
+    /// Crear, modificar y eliminar usuarios
+    #[serde(rename = "users:write")]
+    UsersWrite,
+}
+
+impl Scope {
+    /// Nombre del scope, igual que en JSON y en la columna `api_keys.scopes`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::UsersRead => "users:read",
+            Self::UsersWrite => "users:write",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [Self::UsersRead, Self::UsersWrite].into_iter().find(|scope| scope.as_str() == value)
+    }
+}
+
+/// API key para clientes sin login interactivo; la clave en claro no se guarda.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    /// Primeros caracteres de la clave, para reconocerla
+    #[schema(example = "sk_Ab3dE9")]
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    /// Sin fecha la clave no caduca
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Cuerpo de `POST /api-keys`.
+#[derive(Debug, Deserialize, ToSchema, Validate)]
+pub struct CreateApiKey {
+    #[validate(
+        length(min = 1, max = 100),
+        custom(function = validation::no_control_chars)
+    )]
+    #[schema(min_length = 1, max_length = 100, example = "exportación nocturna")]
+    pub name: String,
+    #[validate(custom(function = validation::not_empty))]
+    #[schema(min_items = 1)]
+    pub scopes: Vec<Scope>,
+    #[serde(default)]
+    #[validate(custom(function = validation::in_future))]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Normalize for CreateApiKey {
+    fn normalize(&mut self) {
+        validation::trim(&mut self.name);
+        self.scopes.sort();
+        self.scopes.dedup();
+    }
+}
+
+/// API key recién creada: es la única vez que se devuelve `key`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedApiKey {
+    /// Valor para la cabecera `X-API-Key`
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+}
+
+/// Filtros y orden aceptados por `GET /users`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserListQuery {
+    /// Nombre que contiene el texto, sin distinguir mayúsculas
+    pub name_contains: Option<String>,
+    /// Dominio exacto del email, p. ej. `example.com`
+    pub email_domain: Option<String>,
+    /// Solo usuarios creados después de esta fecha (RFC 3339)
+    pub created_after: Option<DateTime<Utc>>,
+    /// Solo usuarios modificados después de esta fecha (RFC 3339), para sincronizar
+    /// de forma incremental; con `include_deleted=true` incluye los eliminados desde entonces
+    pub updated_after: Option<DateTime<Utc>>,
+    /// Búsqueda de texto completo sobre nombre y email
+    pub q: Option<String>,
+    /// Campos separados por comas, `-` para orden descendente
+    /// (`id`, `name`, `email`, `created_at`, `updated_at`)
+    #[param(example = "-name,email")]
+    pub sort: Option<String>,
+    /// Incluye los usuarios eliminados; solo para administradores
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+
+/// Parámetros de `GET /users/{id}`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserQuery {
+    /// Devuelve el usuario aunque esté eliminado; solo para administradores
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+
+/// Filtros ya normalizados para el repositorio; los textos vacíos se descartan.
+#[derive(Debug, Default)]
+pub struct UserFilter {
+    pub name_contains: Option<String>,
+    pub email_domain: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub updated_after: Option<DateTime<Utc>>,
+    pub search: Option<String>,
+    /// Sin esta marca los usuarios eliminados se excluyen
+    pub include_deleted: bool,
+}
+
+impl UserListQuery {
+    pub fn filter(&self) -> UserFilter {
+        let text = |value: &Option<String>| {
+            value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+        };
+
+        UserFilter {
+            name_contains: text(&self.name_contains),
+            email_domain: text(&self.email_domain),
+            created_after: self.created_after,
+            updated_after: self.updated_after,
+            search: text(&self.q),
+            include_deleted: self.include_deleted,
+        }
+    }
+}
+
+/// Campos por los que se puede ordenar el listado de usuarios.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UserSortField {
+    Id,
+    Name,
+    Email,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl SortField for UserSortField {
+    const ALLOWED: &'static [(&'static str, Self)] = &[
+        ("id", Self::Id),
+        ("name", Self::Name),
+        ("email", Self::Email),
+        ("created_at", Self::CreatedAt),
+        ("updated_at", Self::UpdatedAt),
+    ];
+
+    fn column(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Name => "name",
+            Self::Email => "email",
+            Self::CreatedAt => "created_at",
+            Self::UpdatedAt => "updated_at",
+        }
+    }
+
+    fn accepts(self, value: &SortValue) -> bool {
+        matches!(
+            (self, value),
+            (Self::Id, SortValue::Int(_))
+                | (Self::Name | Self::Email, SortValue::Text(_))
+                | (Self::CreatedAt | Self::UpdatedAt, SortValue::Time(_))
+        )
+    }
+}
+
+impl UserSortField {
+    /// Valor del campo para un usuario, usado para generar cursores.
+    pub fn value(self, user: &User) -> SortValue {
+        match self {
+            Self::Id => SortValue::Int(user.id.into()),
+            Self::Name => SortValue::Text(user.name.clone()),
+            Self::Email => SortValue::Text(user.email.clone()),
+            Self::CreatedAt => SortValue::Time(user.created_at),
+            Self::UpdatedAt => SortValue::Time(user.updated_at),
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use actix_web::{web, HttpRequest};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::query::SortValue;
+use crate::response::{AppError, ErrorCode};
+
+/// Tamaño de página cuando el cliente no indica `limit`.
+pub const DEFAULT_LIMIT: u32 = 20;
+/// Tamaño de página máximo permitido.
+pub const MAX_LIMIT: u32 = 100;
+/// Mayor `offset` aceptado: el que cabe en el `BIGINT` de Postgres.
+pub const MAX_OFFSET: u64 = i64::MAX as u64;
+
+/// Prefijo con el que se publica la API (`server.base_path`), para construir los enlaces
+/// de la cabecera `Link` tal y como los ve el cliente.
+#[derive(Debug, Clone, Default)]
+pub struct BasePath(pub String);
+
+/// Parámetros de paginación aceptados por los listados.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    /// Número máximo de elementos por página (1-100, por defecto 20)
+    pub limit: Option<u32>,
+    /// Elementos a saltar (paginación por offset); se ignora si se envía `cursor`
+    pub offset: Option<u64>,
+    /// Cursor opaco devuelto en `next_cursor` / `prev_cursor` (paginación por keyset)
+    pub cursor: Option<String>,
+}
+
+/// Metadatos de paginación que acompañan a `data` en los listados.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PageInfo {
+    /// Total de elementos en la colección
+    pub total: i64,
+    /// Tamaño de página aplicado
+    pub limit: u32,
+    /// Cursor para pedir la página siguiente, si existe
+    pub next_cursor: Option<String>,
+    /// Cursor para pedir la página anterior, si existe
+    pub prev_cursor: Option<String>,
+}
+
+/// Posición desde la que se pide una página.
+///
+/// Las claves son los valores de ordenación de la fila de referencia, en el orden del `sort`.
+#[derive(Debug, Clone)]
+pub enum Position {
+    /// Saltar los primeros `n` elementos.
+    Offset(u64),
+    /// Elementos posteriores a la clave dada.
+    After(Vec<SortValue>),
+    /// Elementos anteriores a la clave dada.
+    Before(Vec<SortValue>),
+}
+
+/// Petición de página ya validada, lista para el repositorio.
+#[derive(Debug, Clone)]
+pub struct PageRequest {
+    pub limit: u32,
+    pub position: Position,
+}
+
+/// Página devuelta por un repositorio, siempre en orden ascendente.
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    /// Quedan más elementos en la dirección en la que se avanzó.
+    pub has_more: bool,
+    /// Valores de ordenación del primer y último elemento, para generar cursores.
+    pub first_key: Option<Vec<SortValue>>,
+    pub last_key: Option<Vec<SortValue>>,
+}
+
+impl<T> Page<T> {
+    /// Recorta la página al tamaño pedido a partir de `limit + 1` filas ya ordenadas
+    /// en la dirección de avance, y la deja en orden ascendente.
+    ///
+    /// `key` extrae los valores de ordenación de cada fila.
+    pub fn from_rows<R>(
+        mut rows: Vec<R>,
+        request: &PageRequest,
+        total: i64,
+        key: impl Fn(&R) -> Vec<SortValue>,
+        into_item: impl Fn(R) -> T,
+    ) -> Self {
+        let has_more = rows.len() > request.limit as usize;
+        rows.truncate(request.limit as usize);
+        if let Position::Before(_) = request.position {
+            rows.reverse();
+        }
+
+        Self {
+            first_key: rows.first().map(&key),
+            last_key: rows.last().map(&key),
+            items: rows.into_iter().map(into_item).collect(),
+            total,
+            has_more,
+        }
+    }
+}
+
+/// Contenido del cursor opaco.
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    #[serde(rename = "d")]
+    direction: Direction,
+    #[serde(rename = "k")]
+    key: Vec<SortValue>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+enum Direction {
+    #[serde(rename = "n")]
+    Next,
+    #[serde(rename = "p")]
+    Prev,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        // Serializar un struct tan simple no puede fallar
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(raw: &str) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(raw).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+impl PageQuery {
+    /// Valida los parámetros y los convierte en una `PageRequest`.
+    ///
+    /// `valid_key` comprueba que las claves del cursor encajan con el orden aplicado;
+    /// un cursor generado con otro `sort` se rechaza.
+    pub fn to_request(&self, valid_key: impl Fn(&[SortValue]) -> bool) -> Result<PageRequest, AppError> {
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+        let position = match &self.cursor {
+            Some(raw) => {
+                let cursor = Cursor::decode(raw)
+                    .filter(|cursor| valid_key(&cursor.key))
+                    .ok_or_else(|| AppError::invalid(ErrorCode::InvalidCursor, "Cursor inválido"))?;
+                match cursor.direction {
+                    Direction::Next => Position::After(cursor.key),
+                    Direction::Prev => Position::Before(cursor.key),
+                }
+            }
+            None => {
+                let offset = self.offset.unwrap_or(0);
+                if offset > MAX_OFFSET {
+                    return Err(AppError::invalid(ErrorCode::InvalidQuery, "offset demasiado grande")
+                        .with_details(json!({ "field": "offset", "max": MAX_OFFSET })));
+                }
+                Position::Offset(offset)
+            }
+        };
+
+        Ok(PageRequest { limit, position })
+    }
+}
+
+impl PageInfo {
+    /// Calcula los cursores de la página siguiente y anterior.
+    pub fn new<T>(request: &PageRequest, page: &Page<T>) -> Self {
+        let next = |key: &Vec<SortValue>| Cursor { direction: Direction::Next, key: key.clone() }.encode();
+        let prev = |key: &Vec<SortValue>| Cursor { direction: Direction::Prev, key: key.clone() }.encode();
+        let (first, last) = (page.first_key.as_ref(), page.last_key.as_ref());
+
+        // Hay página siguiente si se avanzaba hacia delante y sobran elementos,
+        // o si se retrocedió (se viene de una página posterior)
+        let (has_next, has_prev) = match request.position {
+            Position::Offset(offset) => (page.has_more, offset > 0),
+            Position::After(_) => (page.has_more, true),
+            Position::Before(_) => (true, page.has_more),
+        };
+
+        Self {
+            total: page.total,
+            limit: request.limit,
+            next_cursor: last.filter(|_| has_next).map(next),
+            prev_cursor: first.filter(|_| has_prev).map(prev),
+        }
+    }
+
+    /// Construye la cabecera `Link` (RFC 8288) con los enlaces `first`, `prev` y `next`.
+    ///
+    /// Conserva el resto de parámetros de la petición original y antepone el `BasePath`
+    /// registrado en la aplicación, si lo hay.
+    pub fn link_header(&self, req: &HttpRequest) -> String {
+        let path = match req.app_data::<web::Data<BasePath>>() {
+            Some(base) => format!("{}{}", base.0, req.path()),
+            None => req.path().to_string(),
+        };
+        let params: Vec<(String, String)> =
+            serde_urlencoded::from_str(req.query_string()).unwrap_or_default();
+        let base: Vec<(String, String)> = params
+            .into_iter()
+            .filter(|(key, _)| key != "cursor" && key != "offset")
+            .collect();
+
+        let link = |cursor: Option<&str>, rel: &str| {
+            let mut query = base.clone();
+            if let Some(cursor) = cursor {
+                query.push(("cursor".to_string(), cursor.to_string()));
+            }
+            let query = serde_urlencoded::to_string(&query).unwrap_or_default();
+            if query.is_empty() {
+                format!("<{}>; rel=\"{}\"", path, rel)
+            } else {
+                format!("<{}?{}>; rel=\"{}\"", path, query, rel)
+            }
+        };
+
+        let mut links = vec![link(None, "first")];
+        if let Some(cursor) = &self.prev_cursor {
+            links.push(link(Some(cursor), "prev"));
+        }
+        if let Some(cursor) = &self.next_cursor {
+            links.push(link(Some(cursor), "next"));
+        }
+
+        links.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    fn query(offset: Option<u64>, cursor: Option<String>) -> PageQuery {
+        PageQuery { limit: None, offset, cursor }
+    }
+
+    fn page(has_more: bool) -> Page<()> {
+        Page {
+            items: Vec::new(),
+            total: 10,
+            has_more,
+            first_key: Some(vec![SortValue::Int(3)]),
+            last_key: Some(vec![SortValue::Int(4)]),
+        }
+    }
+
+    #[test]
+    fn cursor_se_codifica_y_decodifica() {
+        let cursor = Cursor { direction: Direction::Prev, key: vec![SortValue::Text("ana".into()), SortValue::Int(7)] };
+        let decoded = Cursor::decode(&cursor.encode()).unwrap();
+        assert!(decoded.direction == Direction::Prev);
+        assert_eq!(decoded.key, cursor.key);
+
+        assert!(Cursor::decode("no es base64!").is_none());
+        assert!(Cursor::decode(&URL_SAFE_NO_PAD.encode(b"{}")).is_none());
+    }
+
+    #[test]
+    fn to_request_limita_el_tamano_de_pagina() {
+        let limit = |limit| PageQuery { limit, offset: None, cursor: None }.to_request(|_| true).unwrap().limit;
+        assert_eq!(limit(None), DEFAULT_LIMIT);
+        assert_eq!(limit(Some(0)), 1);
+        assert_eq!(limit(Some(1000)), MAX_LIMIT);
+    }
+
+    #[test]
+    fn to_request_rechaza_offsets_fuera_de_rango() {
+        let request = query(Some(MAX_OFFSET), None).to_request(|_| true).unwrap();
+        assert!(matches!(request.position, Position::Offset(MAX_OFFSET)));
+
+        let err = query(Some(MAX_OFFSET + 1), None).to_request(|_| true).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidQuery);
+    }
+
+    #[test]
+    fn to_request_usa_el_cursor_antes_que_el_offset() {
+        let raw = Cursor { direction: Direction::Next, key: vec![SortValue::Int(5)] }.encode();
+        let request = query(Some(3), Some(raw.clone())).to_request(|_| true).unwrap();
+        assert!(matches!(request.position, Position::After(ref key) if key == &[SortValue::Int(5)]));
+
+        // Un cursor de otro orden se rechaza
+        let err = query(None, Some(raw)).to_request(|_| false).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidCursor);
+    }
+
+    #[test]
+    fn page_info_calcula_los_cursores_segun_la_direccion() {
+        let request = |position| PageRequest { limit: 2, position };
+
+        let info = PageInfo::new(&request(Position::Offset(0)), &page(true));
+        assert!(info.next_cursor.is_some() && info.prev_cursor.is_none());
+
+        let info = PageInfo::new(&request(Position::After(vec![SortValue::Int(2)])), &page(false));
+        assert!(info.next_cursor.is_none() && info.prev_cursor.is_some());
+
+        let info = PageInfo::new(&request(Position::Before(vec![SortValue::Int(5)])), &page(false));
+        assert!(info.next_cursor.is_some() && info.prev_cursor.is_none());
+
+        let next = Cursor::decode(info.next_cursor.as_deref().unwrap()).unwrap();
+        assert!(next.direction == Direction::Next);
+        assert_eq!(next.key, [SortValue::Int(4)]);
+    }
+
+    #[test]
+    fn link_header_conserva_los_parametros_y_antepone_el_base_path() {
+        let info = PageInfo { total: 10, limit: 2, next_cursor: Some("abc".into()), prev_cursor: None };
+        let req = TestRequest::get()
+            .uri("/users?limit=2&offset=4&name=ana")
+            .app_data(web::Data::new(BasePath("/api".into())))
+            .to_http_request();
+
+        assert_eq!(
+            info.link_header(&req),
+            "</api/users?limit=2&name=ana>; rel=\"first\", \
+             </api/users?limit=2&name=ana&cursor=abc>; rel=\"next\""
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use std::sync::OnceLock;
+
+use actix_web::web;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use validator::ValidationError;
+
+use crate::response::AppError;
+
+/// Calcula el hash Argon2id (formato PHC) de una contraseña.
+///
+/// Argon2 es costoso a propósito, así que se ejecuta fuera del hilo del servidor.
+pub async fn hash(password: String) -> Result<String, AppError> {
+    web::block(move || hash_blocking(&password))
+        .await
+        .map_err(|e| {
+            log::error!("Error al calcular el hash de la contraseña: {}", e);
+            AppError::InternalError
+        })?
+}
+
+/// `hash` para la contraseña opcional de `CreateUser`/`UpdateUser`.
+pub async fn hash_optional(password: Option<&str>) -> Result<Option<String>, AppError> {
+    match password {
+        Some(password) => hash(password.to_string()).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+fn hash_blocking(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| {
+            log::error!("Error al calcular el hash de la contraseña: {}", e);
+            AppError::InternalError
+        })
+}
+
+/// Comprueba una contraseña contra su hash.
+///
+/// Sin hash (usuario inexistente o sin contraseña) se verifica contra uno ficticio
+/// para que el tiempo de respuesta no revele si el email está registrado.
+pub async fn verify(password: String, hash: Option<String>) -> bool {
+    web::block(move || {
+        let dummy = dummy_hash();
+        let stored = hash.as_deref().unwrap_or(dummy);
+        let matches = PasswordHash::new(stored)
+            .is_ok_and(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok());
+        matches && hash.is_some()
+    })
+    .await
+    .unwrap_or(false)
+}
+
+fn dummy_hash() -> &'static str {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+    DUMMY.get_or_init(|| hash_blocking("contraseña-ficticia").unwrap_or_default())
+}
+
+/// Reglas de fortaleza: al menos una letra y un dígito.
+///
+/// La longitud (8 a 128 caracteres) se valida aparte con la regla `length`.
+pub fn strength(value: &str) -> Result<(), ValidationError> {
+    let has_letter = value.chars().any(char::is_alphabetic);
+    let has_digit = value.chars().any(|c| c.is_ascii_digit());
+
+    if has_letter && has_digit {
+        Ok(())
+    } else {
+        Err(ValidationError::new("password_weak"))
+    }
+}
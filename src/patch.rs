@@ -0,0 +1,212 @@
+use actix_web::{http::header::CONTENT_TYPE, HttpRequest};
+use json_patch::Patch;
+use serde_json::{json, Value};
+
+use crate::models::{CreateUser, UpdateUser, User};
+use crate::response::{AppError, ErrorCode};
+
+/// Content-Type de JSON Merge Patch (RFC 7396).
+pub const MERGE_PATCH: &str = "application/merge-patch+json";
+/// Content-Type de JSON Patch (RFC 6902).
+pub const JSON_PATCH: &str = "application/json-patch+json";
+
+/// Campos de `User` que mantiene el servidor: un parche no puede cambiarlos.
+const IMMUTABLE_FIELDS: &[&str] = &[
+    "id",
+    "role",
+    "created_at",
+    "updated_at",
+    "created_by",
+    "updated_by",
+    "created_by_api_key",
+    "updated_by_api_key",
+];
+
+/// Cuerpo de una petición `PATCH`, según su Content-Type.
+pub enum UserPatch {
+    /// Documento parcial que se fusiona con el usuario actual.
+    Merge(Value),
+    /// Lista de operaciones que se aplican en orden sobre el usuario actual.
+    Json(Patch),
+}
+
+impl UserPatch {
+    /// Interpreta el cuerpo de la petición.
+    ///
+    /// `application/json` se trata como merge patch, que es lo que envían la mayoría de clientes.
+    pub fn from_request(req: &HttpRequest, body: &[u8]) -> Result<Self, AppError> {
+        let content_type = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_ascii_lowercase());
+
+        match content_type.as_deref() {
+            Some(MERGE_PATCH) | Some("application/json") => serde_json::from_slice(body)
+                .map(Self::Merge)
+                .map_err(|e| invalid_patch("JSON Merge Patch", e)),
+            Some(JSON_PATCH) => serde_json::from_slice(body)
+                .map(Self::Json)
+                .map_err(|e| invalid_patch("JSON Patch", e)),
+            other => Err(AppError::invalid(
+                ErrorCode::UnsupportedMediaType,
+                format!("Content-Type no soportado: use {} o {}", MERGE_PATCH, JSON_PATCH),
+            )
+            .with_details(json!({ "content_type": other, "supported": [MERGE_PATCH, JSON_PATCH] }))),
+        }
+    }
+
+    /// Aplica el parche sobre `current` y devuelve el documento resultante completo.
+    ///
+    /// El resultado debe seguir siendo un usuario válido: el `id`, el `role` y los metadatos
+    /// de auditoría no pueden cambiar y `name`/`email` no pueden eliminarse.
+    pub fn apply(&self, current: &User) -> Result<CreateUser, AppError> {
+        let original = serde_json::to_value(current).map_err(|e| {
+            log::error!("Error al serializar usuario {}: {}", current.id, e);
+            AppError::InternalError
+        })?;
+        let mut doc = original.clone();
+
+        match self {
+            Self::Merge(patch) => json_patch::merge(&mut doc, patch),
+            Self::Json(patch) => json_patch::patch(&mut doc, patch).map_err(|e| {
+                log::debug!("JSON Patch rechazado para usuario {}: {}", current.id, e);
+                AppError::unprocessable(ErrorCode::PatchFailed, format!("No se pudo aplicar el JSON Patch: {}", e))
+                    .with_details(json!({ "reason": e.to_string() }))
+            })?,
+        }
+
+        // El rol solo lo asigna `ADMIN_EMAIL` al arrancar. Un `null` en un merge patch elimina
+        // el campo: si ya era `null` no es un cambio
+        for &field in IMMUTABLE_FIELDS {
+            let value = |doc: &Value| doc.get(field).cloned().unwrap_or(Value::Null);
+            if value(&doc) != value(&original) {
+                return Err(AppError::unprocessable(
+                    ErrorCode::ImmutableField,
+                    format!("El campo {} no se puede modificar", field),
+                )
+                .with_details(json!({ "field": field })));
+            }
+        }
+
+        serde_json::from_value(doc).map_err(|_| {
+            AppError::invalid(ErrorCode::MissingFields, "Campos requeridos: name, email")
+                .with_details(json!({ "fields": ["name", "email"] }))
+        })
+    }
+}
+
+fn invalid_patch(format: &str, err: serde_json::Error) -> AppError {
+    AppError::invalid(ErrorCode::InvalidPatch, format!("{} inválido: {}", format, err))
+        .with_details(json!({ "format": format, "reason": err.to_string() }))
+}
+
+/// Calcula los campos que cambian entre el usuario actual y el documento parcheado.
+pub fn changes(current: &User, patched: CreateUser) -> UpdateUser {
+    UpdateUser {
+        name: (patched.name != current.name).then_some(patched.name),
+        email: (patched.email != current.email).then_some(patched.email),
+        // `User` no incluye la contraseña: si el parche la trae, siempre es un cambio
+        password: patched.password,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use chrono::Utc;
+
+    use super::*;
+    use crate::models::Role;
+
+    fn user() -> User {
+        let now = Utc::now();
+        User {
+            id: 7,
+            name: "Ana".into(),
+            email: "ana@example.com".into(),
+            role: Role::User,
+            created_at: now,
+            updated_at: now,
+            created_by: None,
+            updated_by: None,
+            created_by_api_key: None,
+            updated_by_api_key: None,
+            deleted_at: None,
+        }
+    }
+
+    fn patch(content_type: &str, body: Value) -> Result<UserPatch, AppError> {
+        let req = TestRequest::patch().insert_header((CONTENT_TYPE, content_type)).to_http_request();
+        UserPatch::from_request(&req, body.to_string().as_bytes())
+    }
+
+    #[test]
+    fn from_request_elige_el_formato_por_content_type() {
+        assert!(matches!(patch(MERGE_PATCH, json!({})), Ok(UserPatch::Merge(_))));
+        assert!(matches!(patch("application/json; charset=utf-8", json!({})), Ok(UserPatch::Merge(_))));
+        assert!(matches!(patch(JSON_PATCH, json!([])), Ok(UserPatch::Json(_))));
+
+        let err = patch("text/plain", json!({})).err().unwrap();
+        assert_eq!(err.code(), ErrorCode::UnsupportedMediaType);
+        let err = patch(JSON_PATCH, json!({ "op": "add" })).err().unwrap();
+        assert_eq!(err.code(), ErrorCode::InvalidPatch);
+    }
+
+    #[test]
+    fn merge_conserva_los_campos_no_enviados() {
+        let patched = patch(MERGE_PATCH, json!({ "name": "Ana María" })).unwrap().apply(&user()).unwrap();
+        assert_eq!(patched.name, "Ana María");
+        assert_eq!(patched.email, "ana@example.com");
+        assert!(patched.password.is_none());
+    }
+
+    #[test]
+    fn json_patch_aplica_las_operaciones_en_orden() {
+        let ops = json!([
+            { "op": "test", "path": "/name", "value": "Ana" },
+            { "op": "replace", "path": "/email", "value": "ana@example.org" },
+            { "op": "add", "path": "/password", "value": "Secreta-123" },
+        ]);
+        let patched = patch(JSON_PATCH, ops).unwrap().apply(&user()).unwrap();
+        assert_eq!(patched.email, "ana@example.org");
+        assert_eq!(patched.password.as_deref(), Some("Secreta-123"));
+
+        let ops = json!([{ "op": "test", "path": "/name", "value": "Bea" }]);
+        let err = patch(JSON_PATCH, ops).unwrap().apply(&user()).err().unwrap();
+        assert_eq!(err.code(), ErrorCode::PatchFailed);
+    }
+
+    #[test]
+    fn rechaza_cambios_en_campos_inmutables() {
+        for field in IMMUTABLE_FIELDS {
+            let err = patch(MERGE_PATCH, json!({ *field: 99 })).unwrap().apply(&user()).err().unwrap();
+            assert_eq!(err.code(), ErrorCode::ImmutableField, "{}", field);
+        }
+
+        // Escribir el mismo valor no es un cambio
+        let patched = patch(MERGE_PATCH, json!({ "id": 7, "role": "user", "created_by": null })).unwrap().apply(&user());
+        assert!(patched.is_ok());
+    }
+
+    #[test]
+    fn rechaza_eliminar_campos_obligatorios() {
+        let err = patch(MERGE_PATCH, json!({ "email": null })).unwrap().apply(&user()).err().unwrap();
+        assert_eq!(err.code(), ErrorCode::MissingFields);
+    }
+
+    #[test]
+    fn changes_solo_incluye_lo_que_cambia() {
+        let current = user();
+        let same = CreateUser { name: "Ana".into(), email: "ana@example.com".into(), password: None };
+        let unchanged = changes(&current, same);
+        assert!(unchanged.name.is_none() && unchanged.email.is_none() && unchanged.password.is_none());
+
+        let other = CreateUser { name: "Ana".into(), email: "ana@example.org".into(), password: Some("x".into()) };
+        let changed = changes(&current, other);
+        assert!(changed.name.is_none());
+        assert_eq!(changed.email.as_deref(), Some("ana@example.org"));
+        assert_eq!(changed.password.as_deref(), Some("x"));
+    }
+}
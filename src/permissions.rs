@@ -0,0 +1,211 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::{env, io};
+
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use serde_json::json;
+
+use crate::auth::Principal;
+use crate::models::{CreateUser, Role, Scope, User};
+use crate::repository::{Actor, RepoError, UserRepository};
+use crate::response::AppError;
+use crate::{password, validation};
+
+/// Operaciones que un handler puede exigir antes de ejecutarse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Permission {
+    ListUsers,
+    CreateUser,
+    DeleteUser,
+    /// Recuperar un usuario eliminado
+    RestoreUser,
+    /// Ver los usuarios eliminados (`include_deleted=true`)
+    ReadDeletedUsers,
+    /// Leer el usuario con ese ID
+    ReadUser(i32),
+    /// Modificar el usuario con ese ID
+    UpdateUser(i32),
+    /// Cambiar la contraseña del usuario con ese ID
+    SetPassword(i32),
+    /// Modificar o eliminar una cuenta de administrador
+    ModifyAdmin,
+    /// Crear, listar y revocar API keys
+    ManageApiKeys,
+}
+
+impl Permission {
+    /// Scope que debe tener una API key para esta operación; `None` si ninguno la concede.
+    fn scope(self) -> Option<Scope> {
+        match self {
+            Self::ListUsers | Self::ReadUser(_) => Some(Scope::UsersRead),
+            Self::CreateUser | Self::DeleteUser | Self::RestoreUser | Self::UpdateUser(_) => {
+                Some(Scope::UsersWrite)
+            }
+            // Con ellas una API key podría apropiarse de una cuenta de administrador
+            Self::SetPassword(_) | Self::ModifyAdmin => None,
+            Self::ReadDeletedUsers | Self::ManageApiKeys => None,
+        }
+    }
+}
+
+impl Role {
+    /// Indica si el rol concede `permission` a `user_id`.
+    fn grants(self, permission: Permission, user_id: i32) -> bool {
+        match (self, permission) {
+            (Self::Admin, _) => true,
+            (Self::User, Permission::ReadUser(id) | Permission::UpdateUser(id) | Permission::SetPassword(id)) => {
+                id == user_id
+            }
+            (Self::User, _) => false,
+        }
+    }
+}
+
+/// Identidad autenticada junto con su rol en la base de datos (o los scopes de su API key).
+///
+/// Se usa como extractor en los handlers, que declaran con `require` qué
+/// permiso necesitan antes de tocar el repositorio.
+#[derive(Debug)]
+pub struct Authorization {
+    principal: Principal,
+    /// Usuario al que corresponde el `sub` del token, si existe
+    user: Option<(i32, Role)>,
+}
+
+impl Authorization {
+    /// Claim `sub` del token, para los logs.
+    pub fn subject(&self) -> &str {
+        &self.principal.subject
+    }
+
+    /// ID del usuario autenticado; `None` para API keys y tokens de sujetos desconocidos.
+    pub fn user_id(&self) -> Option<i32> {
+        self.user.map(|(id, _)| id)
+    }
+
+    /// Autor de los cambios que se hagan en esta petición.
+    ///
+    /// Un token de un sujeto desconocido no tiene permisos de escritura, así que
+    /// `Actor::System` no llega a anotarse por una petición.
+    pub fn actor(&self) -> Actor {
+        match (self.user, self.principal.api_key_id) {
+            (Some((id, _)), _) => Actor::User(id),
+            (None, Some(key_id)) => Actor::ApiKey(key_id),
+            (None, None) => Actor::System,
+        }
+    }
+
+    /// Devuelve 403 si el usuario o la API key no tienen `permission`.
+    ///
+    /// Un token cuyo `sub` no es el ID de un usuario existente no tiene ningún permiso.
+    pub fn require(&self, permission: Permission) -> Result<(), AppError> {
+        let allowed = match (&self.principal.scopes, self.user) {
+            (Some(scopes), _) => permission.scope().is_some_and(|scope| scopes.contains(&scope)),
+            (None, Some((id, role))) => role.grants(permission, id),
+            (None, None) => false,
+        };
+        if allowed {
+            return Ok(());
+        }
+
+        log::debug!("Permiso {:?} denegado a {}", permission, self.principal.subject);
+        let err = AppError::forbidden("No tiene permisos para esta operación");
+        // A una API key se le indica qué scope le falta
+        match (&self.principal.scopes, permission.scope()) {
+            (Some(_), Some(scope)) => Err(err.with_details(json!({ "scope": scope }))),
+            _ => Err(err),
+        }
+    }
+
+    /// Exige los permisos para modificar `target` y, si `password`, cambiar su contraseña.
+    ///
+    /// `users:write` no basta para modificar administradores ni contraseñas.
+    pub fn require_update(&self, target: &User, password: bool) -> Result<(), AppError> {
+        self.require(Permission::UpdateUser(target.id))?;
+        if target.role == Role::Admin {
+            self.require(Permission::ModifyAdmin)?;
+        }
+        if password {
+            self.require(Permission::SetPassword(target.id))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromRequest for Authorization {
+    type Error = AppError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let principal = Principal::from_request(req, payload).into_inner();
+        let repo = req.app_data::<web::Data<dyn UserRepository>>().cloned();
+
+        Box::pin(async move {
+            let principal = principal?;
+            let Some(repo) = repo else {
+                log::error!("UserRepository no está registrado en la aplicación");
+                return Err(AppError::InternalError);
+            };
+
+            if principal.scopes.is_some() {
+                return Ok(Self { principal, user: None });
+            }
+
+            // El rol se lee en cada petición para que un cambio se aplique sin esperar a que caduque el token
+            let user = match principal.subject.parse::<i32>() {
+                Ok(id) => match repo.get(id).await {
+                    Ok(user) => Some((user.id, user.role)),
+                    Err(RepoError::NotFound) => None,
+                    Err(e) => {
+                        log::error!("Error al obtener el rol del usuario {}: {}", id, e);
+                        return Err(e.into());
+                    }
+                },
+                Err(_) => None,
+            };
+
+            Ok(Self { principal, user })
+        })
+    }
+}
+
+/// Crea o promociona el administrador inicial.
+///
+/// Si `ADMIN_EMAIL` corresponde a un usuario existente se le asigna el rol `admin`;
+/// si no, se crea con la contraseña de `ADMIN_PASSWORD`. Sin `ADMIN_EMAIL` no hace nada.
+pub async fn bootstrap_admin(repo: &dyn UserRepository) -> io::Result<()> {
+    let Ok(email) = env::var("ADMIN_EMAIL") else {
+        return Ok(());
+    };
+
+    let admin = validation::validate(CreateUser {
+        name: "Administrador".to_string(),
+        email,
+        password: env::var("ADMIN_PASSWORD").ok(),
+    })
+    .map_err(|_| io::Error::other("ADMIN_EMAIL o ADMIN_PASSWORD no son válidos"))?;
+
+    let user = match repo.credentials(&admin.email).await {
+        Ok(existing) => existing.user,
+        Err(RepoError::NotFound) => {
+            if admin.password.is_none() {
+                return Err(io::Error::other(format!(
+                    "El usuario {} no existe: defina ADMIN_PASSWORD para crearlo",
+                    admin.email
+                )));
+            }
+            let password_hash = password::hash_optional(admin.password.as_deref())
+                .await
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            repo.create(&admin, password_hash.as_deref(), Actor::System).await.map_err(io::Error::other)?
+        }
+        Err(e) => return Err(io::Error::other(e)),
+    };
+
+    if user.role != Role::Admin {
+        repo.set_role(user.id, Role::Admin).await.map_err(io::Error::other)?;
+        log::info!("Usuario {} promocionado a administrador", user.email);
+    }
+
+    Ok(())
+}
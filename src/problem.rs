@@ -0,0 +1,191 @@
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{self, HeaderValue},
+    middleware::Next,
+    web, Error, HttpRequest, HttpResponse,
+};
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::openapi::{ContentBuilder, Ref, RefOr};
+use utoipa::{Modify, ToSchema};
+
+use crate::config::{ErrorsConfig, ProblemDetailsMode};
+use crate::response::{AppError, ErrorCode};
+use crate::i18n::Locale;
+use crate::request_id;
+use crate::validation::FieldMessages;
+
+/// Content-Type de Problem Details (RFC 7807).
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Representación de un error según RFC 7807.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProblemDetails {
+    /// URI que identifica el tipo de problema (`about:blank` si solo aplica la semántica del status)
+    #[serde(rename = "type")]
+    #[schema(example = "about:blank")]
+    pub problem_type: String,
+    /// Resumen legible del tipo de problema
+    #[schema(example = "Not Found")]
+    pub title: String,
+    /// Código de estado HTTP
+    #[schema(example = 404)]
+    pub status: u16,
+    /// Explicación específica de esta ocurrencia
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "Usuario no encontrado")]
+    pub detail: Option<String>,
+    /// Ruta de la petición que originó el problema
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "/users/42")]
+    pub instance: Option<String>,
+    /// Identificador de la petición, para correlacionar con los logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Código de error estable (el mismo que `ErrModel.code`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+    /// Información adicional del error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<Value>,
+    /// Errores de validación por campo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<HashMap<String, Vec<String>>>)]
+    pub errors: Option<FieldMessages>,
+}
+
+impl ProblemDetails {
+    /// Problema genérico para un código de estado, con `type` `about:blank`.
+    pub fn new(status: actix_web::http::StatusCode, detail: Option<String>) -> Self {
+        Self {
+            problem_type: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail,
+            instance: None,
+            request_id: None,
+            code: None,
+            details: None,
+            errors: None,
+        }
+    }
+
+    /// Completa los miembros que dependen de la petición.
+    pub fn for_request(mut self, req: &HttpRequest) -> Self {
+        self.instance = Some(req.path().to_string());
+        self.request_id = request_id::current();
+        self
+    }
+
+    pub fn into_response(self) -> HttpResponse {
+        let status = actix_web::http::StatusCode::from_u16(self.status)
+            .unwrap_or(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR);
+
+        HttpResponse::build(status)
+            .insert_header((header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON)))
+            .json(self)
+    }
+}
+
+/// Cuándo se responden los errores como Problem Details.
+#[derive(Debug, Clone, Copy)]
+pub struct ProblemConfig {
+    /// Siempre, sin importar la cabecera `Accept`.
+    pub always: bool,
+}
+
+impl ProblemConfig {
+    /// Con `problem_details = "always"` se usan siempre; si no, solo cuando el cliente
+    /// envía `Accept: application/problem+json`.
+    pub fn from_config(config: &ErrorsConfig) -> Self {
+        Self { always: config.problem_details == ProblemDetailsMode::Always }
+    }
+
+    fn wants_problem(&self, req: &HttpRequest) -> bool {
+        self.always
+            || req
+                .headers()
+                .get_all(header::ACCEPT)
+                .filter_map(|value| value.to_str().ok())
+                .any(|value| value.contains(PROBLEM_JSON))
+    }
+}
+
+/// Middleware que da formato final a las respuestas de error.
+///
+/// Los `AppError` se traducen al idioma de `Accept-Language` y se responden como
+/// Problem Details cuando el cliente o la configuración lo piden. Los demás errores
+/// (p. ej. los de extractores sin manejador propio) solo se convierten a Problem Details,
+/// ocultando el detalle de los 5xx.
+pub async fn render_errors(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let wants_problem = req
+        .app_data::<web::Data<ProblemConfig>>()
+        .is_some_and(|config| config.wants_problem(req.request()));
+    let locale = Locale::from_request(req.request());
+
+    // Los errores de los handlers llegan dentro de la respuesta; un `Err` aquí
+    // solo puede venir de otro middleware y se deja pasar tal cual
+    let res = next.call(req).await?;
+    let Some(err) = res.response().error() else {
+        return Ok(res.map_into_boxed_body());
+    };
+
+    let mut response = match (err.as_error::<AppError>(), wants_problem) {
+        (Some(app_err), true) => {
+            let mut response = app_err.problem(locale).for_request(res.request()).into_response();
+            response
+                .headers_mut()
+                .insert(header::CONTENT_LANGUAGE, HeaderValue::from_static(locale.tag()));
+            response
+        }
+        (Some(app_err), false) => app_err.localized_response(locale),
+        (None, true) => {
+            let status = res.status();
+            let detail = (!status.is_server_error()).then(|| err.to_string());
+            ProblemDetails::new(status, detail).for_request(res.request()).into_response()
+        }
+        (None, false) => return Ok(res.map_into_boxed_body()),
+    };
+
+    // Conserva las cabeceras propias del error (p. ej. `WWW-Authenticate`)
+    for (name, value) in res.headers() {
+        if !response.headers().contains_key(name) {
+            response.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+
+    Ok(res.into_response(response))
+}
+
+/// Documenta los dos formatos de error en todas las respuestas 4xx/5xx del OpenAPI.
+///
+/// Las respuestas que ya declaran su cuerpo (p. ej. el 503 de `/health`) se dejan como están.
+pub struct ErrorResponses;
+
+impl Modify for ErrorResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for item in openapi.paths.paths.values_mut() {
+            for operation in item.operations.values_mut() {
+                for (status, response) in operation.responses.responses.iter_mut() {
+                    let RefOr::T(response) = response else { continue };
+                    if !status.starts_with('4') && !status.starts_with('5') || !response.content.is_empty() {
+                        continue;
+                    }
+                    response.content.insert(
+                        "application/json".to_string(),
+                        ContentBuilder::new().schema(Ref::from_schema_name("ErrModel")).build(),
+                    );
+                    response.content.insert(
+                        PROBLEM_JSON.to_string(),
+                        ContentBuilder::new().schema(Ref::from_schema_name("ProblemDetails")).build(),
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Piezas reutilizables para componer consultas de listado con SQL parametrizado.
+//!
+//! Los nombres de columna solo salen de listas blancas (`SortField::column`) o de
+//! literales del código; los valores del cliente siempre se envían con `push_bind`.
+
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+
+/// Dirección de ordenación de una clave.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Campo por el que se permite ordenar una colección.
+pub trait SortField: Sized + Copy + PartialEq + 'static {
+    /// Nombres aceptados en el parámetro `sort` y el campo al que corresponden.
+    const ALLOWED: &'static [(&'static str, Self)];
+
+    /// Columna SQL del campo.
+    fn column(self) -> &'static str;
+
+    /// Indica si `value` es del tipo de la columna.
+    fn accepts(self, value: &SortValue) -> bool;
+}
+
+/// Clave de ordenación: campo y dirección.
+#[derive(Debug, Clone, Copy)]
+pub struct SortKey<F> {
+    pub field: F,
+    pub order: SortOrder,
+}
+
+/// Ordenación completa de un listado.
+///
+/// Siempre termina en un campo único (`tiebreaker`) para que el orden sea total
+/// y la paginación por keyset no repita ni salte filas.
+#[derive(Debug, Clone)]
+pub struct Sort<F> {
+    keys: Vec<SortKey<F>>,
+}
+
+/// Valor de una clave de ordenación, tal y como viaja dentro de los cursores.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SortValue {
+    #[serde(rename = "i")]
+    Int(i64),
+    #[serde(rename = "s")]
+    Text(String),
+    #[serde(rename = "t")]
+    Time(DateTime<Utc>),
+}
+
+impl SortValue {
+    fn push_bind(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            Self::Int(value) => query.push_bind(*value),
+            Self::Text(value) => query.push_bind(value.clone()),
+            Self::Time(value) => query.push_bind(*value),
+        };
+    }
+}
+
+impl PartialOrd for SortValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a.partial_cmp(b),
+            (Self::Text(a), Self::Text(b)) => a.partial_cmp(b),
+            (Self::Time(a), Self::Time(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+impl<F: SortField> Sort<F> {
+    /// Interpreta una lista como `-name,email` (el prefijo `-` indica orden descendente).
+    ///
+    /// Devuelve el nombre del campo problemático si no está en la lista blanca o se repite.
+    pub fn parse(raw: Option<&str>, tiebreaker: F) -> Result<Self, String> {
+        let mut keys: Vec<SortKey<F>> = Vec::new();
+
+        for item in raw.unwrap_or_default().split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (name, order) = match item.strip_prefix('-') {
+                Some(name) => (name, SortOrder::Desc),
+                None => (item.strip_prefix('+').unwrap_or(item), SortOrder::Asc),
+            };
+            let field = F::ALLOWED
+                .iter()
+                .find(|(allowed, _)| *allowed == name)
+                .map(|(_, field)| *field)
+                .ok_or_else(|| name.to_string())?;
+            if keys.iter().any(|key| key.field == field) {
+                return Err(name.to_string());
+            }
+            keys.push(SortKey { field, order });
+        }
+
+        if !keys.iter().any(|key| key.field == tiebreaker) {
+            keys.push(SortKey { field: tiebreaker, order: SortOrder::Asc });
+        }
+
+        Ok(Self { keys })
+    }
+
+    pub fn keys(&self) -> &[SortKey<F>] {
+        &self.keys
+    }
+
+    /// Comprueba que unos valores (p. ej. los de un cursor) corresponden a este orden.
+    pub fn accepts(&self, values: &[SortValue]) -> bool {
+        values.len() == self.keys.len()
+            && self.keys.iter().zip(values).all(|(key, value)| key.field.accepts(value))
+    }
+
+    /// Añade `ORDER BY ...`; con `reverse` invierte todas las direcciones.
+    pub fn push_order_by(&self, query: &mut QueryBuilder<'_, Postgres>, reverse: bool) {
+        query.push(" ORDER BY ");
+        let mut separated = query.separated(", ");
+        for key in &self.keys {
+            let desc = (key.order == SortOrder::Desc) != reverse;
+            separated.push(format!("{} {}", key.field.column(), if desc { "DESC" } else { "ASC" }));
+        }
+    }
+
+    /// Añade la condición de keyset para continuar después de `values`
+    /// (o antes, con `reverse`), siguiendo el orden de las claves.
+    ///
+    /// Genera `(k1 > v1) OR (k1 = v1 AND k2 > v2) OR ...`, que admite direcciones mixtas.
+    pub fn push_keyset(&self, query: &mut QueryBuilder<'_, Postgres>, values: &[SortValue], reverse: bool) {
+        query.push("(");
+        for (i, key) in self.keys.iter().enumerate() {
+            if i > 0 {
+                query.push(" OR ");
+            }
+            query.push("(");
+            for (prev, value) in self.keys[..i].iter().zip(values) {
+                query.push(prev.field.column()).push(" = ");
+                value.push_bind(query);
+                query.push(" AND ");
+            }
+            let greater = (key.order == SortOrder::Asc) != reverse;
+            query.push(key.field.column()).push(if greater { " > " } else { " < " });
+            values[i].push_bind(query);
+            query.push(")");
+        }
+        query.push(")");
+    }
+
+    /// Compara dos filas a partir de sus valores de ordenación (para backends en memoria).
+    pub fn compare(&self, a: &[SortValue], b: &[SortValue]) -> Ordering {
+        for (key, (a, b)) in self.keys.iter().zip(a.iter().zip(b)) {
+            let ordering = a.partial_cmp(b).unwrap_or(Ordering::Equal);
+            let ordering = match key.order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+/// Ayuda a encadenar condiciones con `WHERE` / `AND`.
+#[derive(Default)]
+pub struct WhereClause {
+    started: bool,
+}
+
+impl WhereClause {
+    /// Abre una nueva condición; el llamador añade a continuación la expresión.
+    pub fn and<'q, 'args>(&mut self, query: &'q mut QueryBuilder<'args, Postgres>) -> &'q mut QueryBuilder<'args, Postgres> {
+        query.push(if self.started { " AND " } else { " WHERE " });
+        self.started = true;
+        query
+    }
+}
+
+/// Escapa `%`, `_` y `\` para usar un valor del cliente dentro de un patrón `LIKE`.
+pub fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UserSortField;
+
+    fn fields(sort: &Sort<UserSortField>) -> Vec<(UserSortField, SortOrder)> {
+        sort.keys().iter().map(|key| (key.field, key.order)).collect()
+    }
+
+    #[test]
+    fn parse_anade_el_desempate_al_final() {
+        let sort = Sort::parse(Some("-name, +email"), UserSortField::Id).unwrap();
+        assert_eq!(
+            fields(&sort),
+            [
+                (UserSortField::Name, SortOrder::Desc),
+                (UserSortField::Email, SortOrder::Asc),
+                (UserSortField::Id, SortOrder::Asc),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_respeta_el_desempate_explicito() {
+        let sort = Sort::parse(Some("-id"), UserSortField::Id).unwrap();
+        assert_eq!(fields(&sort), [(UserSortField::Id, SortOrder::Desc)]);
+
+        let sort = Sort::parse(None, UserSortField::Id).unwrap();
+        assert_eq!(fields(&sort), [(UserSortField::Id, SortOrder::Asc)]);
+    }
+
+    #[test]
+    fn parse_rechaza_campos_desconocidos_o_repetidos() {
+        assert_eq!(Sort::parse(Some("password"), UserSortField::Id).unwrap_err(), "password");
+        assert_eq!(Sort::parse(Some("name,-name"), UserSortField::Id).unwrap_err(), "name");
+    }
+
+    #[test]
+    fn accepts_comprueba_numero_y_tipo_de_las_claves() {
+        let sort = Sort::parse(Some("name"), UserSortField::Id).unwrap();
+        assert!(sort.accepts(&[SortValue::Text("ana".into()), SortValue::Int(1)]));
+        assert!(!sort.accepts(&[SortValue::Int(1), SortValue::Text("ana".into())]));
+        assert!(!sort.accepts(&[SortValue::Text("ana".into())]));
+    }
+
+    #[test]
+    fn push_keyset_genera_condiciones_con_direcciones_mixtas() {
+        let sort = Sort::parse(Some("-name"), UserSortField::Id).unwrap();
+        let values = [SortValue::Text("ana".into()), SortValue::Int(7)];
+
+        let mut query = QueryBuilder::<Postgres>::new("");
+        sort.push_keyset(&mut query, &values, false);
+        assert_eq!(query.sql(), "((name < $1) OR (name = $2 AND id > $3))");
+
+        let mut query = QueryBuilder::<Postgres>::new("");
+        sort.push_keyset(&mut query, &values, true);
+        assert_eq!(query.sql(), "((name > $1) OR (name = $2 AND id < $3))");
+    }
+
+    #[test]
+    fn push_order_by_invierte_las_direcciones() {
+        let sort = Sort::parse(Some("-created_at"), UserSortField::Id).unwrap();
+
+        let mut query = QueryBuilder::<Postgres>::new("SELECT");
+        sort.push_order_by(&mut query, false);
+        assert_eq!(query.sql(), "SELECT ORDER BY created_at DESC, id ASC");
+
+        let mut query = QueryBuilder::<Postgres>::new("SELECT");
+        sort.push_order_by(&mut query, true);
+        assert_eq!(query.sql(), "SELECT ORDER BY created_at ASC, id DESC");
+    }
+
+    #[test]
+    fn compare_sigue_el_orden_de_las_claves() {
+        let sort = Sort::parse(Some("-name"), UserSortField::Id).unwrap();
+        let row = |name: &str, id: i64| [SortValue::Text(name.into()), SortValue::Int(id)];
+
+        assert_eq!(sort.compare(&row("bea", 1), &row("ana", 2)), Ordering::Less);
+        assert_eq!(sort.compare(&row("ana", 1), &row("ana", 2)), Ordering::Less);
+        assert_eq!(sort.compare(&row("ana", 2), &row("ana", 2)), Ordering::Equal);
+    }
+
+    #[test]
+    fn escape_like_escapa_los_comodines() {
+        assert_eq!(escape_like(r"50%_a\b"), r"50\%\_a\\b");
+    }
+}
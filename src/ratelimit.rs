@@ -0,0 +1,295 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+        Method,
+    },
+    middleware::Next,
+    web, Error,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+
+use crate::auth;
+use crate::config::{LimitConfig, RateLimitConfig};
+use crate::repository::RateLimitStore;
+use crate::response::AppError;
+use crate::shutdown::Shutdown;
+
+/// Cada cuánto se eliminan los buckets que ya no se usan.
+const PURGE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Límite de un grupo de rutas: `capacity` peticiones repuestas a lo largo de `period`.
+///
+/// Permite ráfagas de hasta `capacity` peticiones; después, una cada `period / capacity`.
+#[derive(Debug, Clone)]
+pub struct Policy {
+    /// Nombre del grupo, forma parte de la clave del bucket
+    pub name: &'static str,
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+/// Estado guardado de un token bucket.
+#[derive(Debug, Clone, Copy)]
+pub struct Bucket {
+    pub tokens: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Resultado de pedir un token al bucket.
+#[derive(Debug, Clone, Copy)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    /// Peticiones que quedan disponibles ahora mismo
+    pub remaining: u32,
+    /// Segundos hasta que el bucket vuelva a estar lleno
+    pub reset: u64,
+    /// Segundos hasta que haya al menos un token (0 si la petición pasa)
+    pub retry_after: u64,
+}
+
+impl Policy {
+    fn new(name: &'static str, limit: &LimitConfig) -> Self {
+        Self { name, capacity: limit.requests, period: limit.period() }
+    }
+
+    /// Tokens que se reponen por segundo.
+    fn rate(&self) -> f64 {
+        f64::from(self.capacity) / self.period.as_secs_f64()
+    }
+
+    /// Repone los tokens del tiempo transcurrido e intenta consumir uno.
+    ///
+    /// Sin estado previo el bucket empieza lleno. Devuelve el estado que hay que guardar.
+    pub fn take(&self, bucket: Option<Bucket>, now: DateTime<Utc>) -> (Bucket, Decision) {
+        let capacity = f64::from(self.capacity);
+        let tokens = match bucket {
+            Some(bucket) => {
+                let elapsed = (now - bucket.updated_at).num_milliseconds().max(0) as f64 / 1000.0;
+                (bucket.tokens + elapsed * self.rate()).min(capacity)
+            }
+            None => capacity,
+        };
+
+        let allowed = tokens >= 1.0;
+        let tokens = if allowed { tokens - 1.0 } else { tokens };
+        let seconds_until = |target: f64| ((target - tokens).max(0.0) / self.rate()).ceil() as u64;
+
+        let decision = Decision {
+            allowed,
+            limit: self.capacity,
+            remaining: tokens.floor() as u32,
+            reset: seconds_until(capacity),
+            retry_after: if allowed { 0 } else { seconds_until(1.0) },
+        };
+        (Bucket { tokens, updated_at: now }, decision)
+    }
+}
+
+/// Límites por cliente para las rutas de la API.
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    /// `GET`/`HEAD` sobre `/users` y `/api-keys`
+    read: Policy,
+    /// Escrituras sobre `/users` y `/api-keys`
+    write: Policy,
+    /// `/auth/*`: login, refresh y logout
+    auth: Policy,
+    /// Usar la IP de `Forwarded`/`X-Forwarded-For` en lugar de la del socket
+    trust_proxy: bool,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig, store: Arc<dyn RateLimitStore>) -> Self {
+        Self {
+            store,
+            read: Policy::new("read", &config.read),
+            write: Policy::new("write", &config.write),
+            auth: Policy::new("auth", &config.auth),
+            trust_proxy: config.trust_proxy,
+        }
+    }
+
+    /// Política que aplica a la ruta; `None` para las rutas sin límite (Swagger UI, OpenAPI).
+    fn policy_for(&self, method: &Method, path: &str) -> Option<&Policy> {
+        if path.starts_with("/auth/") {
+            Some(&self.auth)
+        } else if !(path.starts_with("/users") || path.starts_with("/api-keys")) {
+            None
+        } else if matches!(*method, Method::GET | Method::HEAD) {
+            Some(&self.read)
+        } else {
+            Some(&self.write)
+        }
+    }
+
+    /// Identifica al cliente: usuario del token, API key o, si no hay credenciales, IP.
+    ///
+    /// Un token o una API key inválidos cuentan como anónimos: si no, cambiarlos en cada
+    /// petición daría un bucket nuevo cada vez y eludiría el límite de la IP. Las credenciales
+    /// las comprueba `auth::authenticated`, que `require_auth` reutiliza después.
+    async fn client_key(&self, req: &ServiceRequest) -> String {
+        if let Some(principal) = auth::authenticated(req).await {
+            return match principal.api_key_id {
+                Some(_) => principal.subject,
+                None => format!("user:{}", principal.subject),
+            };
+        }
+
+        let info = req.connection_info();
+        let ip = if self.trust_proxy { info.realip_remote_addr() } else { info.peer_addr() };
+        format!("ip:{}", ip.unwrap_or("desconocida"))
+    }
+
+    /// Tarea periódica que borra los buckets que llevan más de un periodo sin usarse
+    /// (ya estarían llenos, así que no hace falta recordarlos).
+    ///
+    /// Al apagar termina la purga en curso antes de salir.
+    pub fn spawn_purge(&self, shutdown: &Shutdown) {
+        let store = self.store.clone();
+        let idle = [&self.read, &self.write, &self.auth]
+            .into_iter()
+            .map(|policy| policy.period)
+            .max()
+            .unwrap_or_default();
+
+        shutdown.spawn_worker("rate-limit-purge", |mut stop| async move {
+            let mut interval = actix_web::rt::time::interval(PURGE_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = stop.stopped() => break,
+                }
+                let before = Utc::now() - chrono::Duration::from_std(idle).unwrap_or_default();
+                match store.purge(before).await {
+                    Ok(0) => {}
+                    Ok(purged) => log::debug!("{} buckets de rate limit eliminados", purged),
+                    Err(e) => log::warn!("No se pudieron eliminar los buckets de rate limit: {}", e),
+                }
+            }
+        });
+    }
+}
+
+/// Middleware que aplica el límite de peticiones de cada ruta.
+///
+/// Añade las cabeceras `RateLimit-Limit`, `RateLimit-Remaining`, `RateLimit-Reset` y
+/// `RateLimit-Policy` a todas las respuestas limitadas, y responde 429 con `Retry-After`
+/// cuando el cliente agota su cupo. Si el almacén falla, la petición pasa.
+pub async fn limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let Some(limiter) = req.app_data::<web::Data<RateLimiter>>().cloned() else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+    let Some(policy) = limiter.policy_for(req.method(), req.path()) else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    let key = format!("{}:{}", policy.name, limiter.client_key(&req).await);
+    let decision = match limiter.store.acquire(&key, policy).await {
+        Ok(decision) => decision,
+        Err(e) => {
+            log::error!("Error del almacén de rate limit: {}", e);
+            return next.call(req).await.map(ServiceResponse::map_into_left_body);
+        }
+    };
+
+    let mut res = if decision.allowed {
+        next.call(req).await?.map_into_left_body()
+    } else {
+        log::debug!("Límite {} superado por {}", policy.name, key);
+        let err = AppError::too_many_requests("Demasiadas peticiones, intente de nuevo más tarde")
+            .with_details(json!({ "retry_after": decision.retry_after }));
+        let mut res = req.error_response(err);
+        insert(res.headers_mut(), RETRY_AFTER, decision.retry_after.to_string());
+        res.map_into_right_body()
+    };
+
+    let headers = res.headers_mut();
+    insert(headers, HeaderName::from_static("ratelimit-limit"), decision.limit.to_string());
+    insert(headers, HeaderName::from_static("ratelimit-remaining"), decision.remaining.to_string());
+    insert(headers, HeaderName::from_static("ratelimit-reset"), decision.reset.to_string());
+    insert(
+        headers,
+        HeaderName::from_static("ratelimit-policy"),
+        format!("{};w={}", policy.capacity, policy.period.as_secs()),
+    );
+
+    Ok(res)
+}
+
+fn insert(headers: &mut HeaderMap, name: HeaderName, value: String) {
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        headers.insert(name, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    #[test]
+    fn take_empieza_con_el_bucket_lleno() {
+        let policy = Policy::new("test", &LimitConfig { requests: 3, period_secs: 60 });
+        let (bucket, decision) = policy.take(None, Utc::now());
+
+        assert!(decision.allowed);
+        assert_eq!(decision.limit, 3);
+        assert_eq!(decision.remaining, 2);
+        // Falta un token, que se repone en 20s
+        assert_eq!(decision.reset, 20);
+        assert_eq!(bucket.tokens, 2.0);
+    }
+
+    #[test]
+    fn take_rechaza_sin_tokens_e_indica_cuando_reintentar() {
+        let policy = Policy::new("test", &LimitConfig { requests: 3, period_secs: 60 });
+        let now = Utc::now();
+        let empty = Bucket { tokens: 0.5, updated_at: now };
+        let (bucket, decision) = policy.take(Some(empty), now);
+
+        assert!(!decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(decision.retry_after, 10);
+        assert_eq!(decision.reset, 50);
+        // Una petición rechazada no consume tokens
+        assert_eq!(bucket.tokens, 0.5);
+    }
+
+    #[test]
+    fn take_repone_tokens_segun_el_tiempo_transcurrido() {
+        let policy = Policy::new("test", &LimitConfig { requests: 3, period_secs: 60 });
+        let start = Utc::now();
+        let empty = Bucket { tokens: 0.0, updated_at: start };
+
+        let (bucket, decision) = policy.take(Some(empty), start + TimeDelta::seconds(30));
+        assert!(decision.allowed);
+        assert!((bucket.tokens - 0.5).abs() < 1e-9);
+
+        // Nunca se acumulan más tokens que la capacidad
+        let (bucket, decision) = policy.take(Some(empty), start + TimeDelta::hours(1));
+        assert!(decision.allowed);
+        assert_eq!(bucket.tokens, 2.0);
+    }
+
+    #[test]
+    fn take_ignora_relojes_que_retroceden() {
+        let policy = Policy::new("test", &LimitConfig { requests: 3, period_secs: 60 });
+        let now = Utc::now();
+        let bucket = Bucket { tokens: 1.0, updated_at: now };
+        let (bucket, decision) = policy.take(Some(bucket), now - TimeDelta::seconds(30));
+
+        assert!(decision.allowed);
+        assert_eq!(bucket.tokens, 0.0);
+    }
+}
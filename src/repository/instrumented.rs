@@ -0,0 +1,135 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::{
+    Actor, ApiKeyRepository, Consumed, Credentials, NewApiKey, RefreshToken, RepoResult, TokenRepository, UserRepository,
+};
+use crate::metrics::Metrics;
+use crate::models::{ApiKey, CreateUser, Role, UpdateUser, User, UserFilter, UserSortField};
+use crate::pagination::{Page, PageRequest};
+use crate::query::Sort;
+use crate::telemetry;
+
+/// Envuelve un repositorio y anota en `Metrics` la duración y el resultado de cada operación,
+/// que además queda como span de la traza en curso.
+pub struct Instrumented<R: ?Sized> {
+    inner: Arc<R>,
+    /// Etiqueta `repository` de las métricas
+    name: &'static str,
+    metrics: Arc<Metrics>,
+}
+
+impl<R: ?Sized> Instrumented<R> {
+    pub fn new(inner: Arc<R>, name: &'static str, metrics: Arc<Metrics>) -> Self {
+        Self { inner, name, metrics }
+    }
+
+    async fn observe<T>(&self, operation: &str, query: impl Future<Output = RepoResult<T>>) -> RepoResult<T> {
+        let start = Instant::now();
+        let result = telemetry::trace_query(self.name, operation, query).await;
+        self.metrics.observe_query(self.name, operation, start.elapsed(), &result);
+        result
+    }
+}
+
+#[async_trait]
+impl<R: UserRepository + ?Sized> UserRepository for Instrumented<R> {
+    async fn list(
+        &self,
+        filter: &UserFilter,
+        sort: &Sort<UserSortField>,
+        page: &PageRequest,
+    ) -> RepoResult<Page<User>> {
+        self.observe("list", self.inner.list(filter, sort, page)).await
+    }
+
+    async fn get(&self, id: i32) -> RepoResult<User> {
+        self.observe("get", self.inner.get(id)).await
+    }
+
+    async fn get_any(&self, id: i32) -> RepoResult<User> {
+        self.observe("get_any", self.inner.get_any(id)).await
+    }
+
+    async fn credentials(&self, email: &str) -> RepoResult<Credentials> {
+        self.observe("credentials", self.inner.credentials(email)).await
+    }
+
+    async fn create(&self, user: &CreateUser, password_hash: Option<&str>, actor: Actor) -> RepoResult<User> {
+        self.observe("create", self.inner.create(user, password_hash, actor)).await
+    }
+
+    async fn update(
+        &self,
+        id: i32,
+        user: &CreateUser,
+        password_hash: Option<&str>,
+        actor: Actor,
+    ) -> RepoResult<User> {
+        self.observe("update", self.inner.update(id, user, password_hash, actor)).await
+    }
+
+    async fn patch(
+        &self,
+        id: i32,
+        changes: &UpdateUser,
+        password_hash: Option<&str>,
+        actor: Actor,
+    ) -> RepoResult<User> {
+        self.observe("patch", self.inner.patch(id, changes, password_hash, actor)).await
+    }
+
+    async fn set_role(&self, id: i32, role: Role) -> RepoResult<User> {
+        self.observe("set_role", self.inner.set_role(id, role)).await
+    }
+
+    async fn delete(&self, id: i32, actor: Actor) -> RepoResult<()> {
+        self.observe("delete", self.inner.delete(id, actor)).await
+    }
+
+    async fn restore(&self, id: i32, actor: Actor) -> RepoResult<User> {
+        self.observe("restore", self.inner.restore(id, actor)).await
+    }
+
+    async fn purge(&self, before: DateTime<Utc>) -> RepoResult<u64> {
+        self.observe("purge", self.inner.purge(before)).await
+    }
+}
+
+#[async_trait]
+impl<R: TokenRepository + ?Sized> TokenRepository for Instrumented<R> {
+    async fn insert(&self, token_hash: &str, token: &RefreshToken) -> RepoResult<()> {
+        self.observe("insert", self.inner.insert(token_hash, token)).await
+    }
+
+    async fn consume(&self, token_hash: &str) -> RepoResult<Consumed> {
+        self.observe("consume", self.inner.consume(token_hash)).await
+    }
+
+    async fn revoke_family(&self, family: &str) -> RepoResult<()> {
+        self.observe("revoke_family", self.inner.revoke_family(family)).await
+    }
+}
+
+#[async_trait]
+impl<R: ApiKeyRepository + ?Sized> ApiKeyRepository for Instrumented<R> {
+    async fn create(&self, key: &NewApiKey<'_>) -> RepoResult<ApiKey> {
+        self.observe("create", self.inner.create(key)).await
+    }
+
+    async fn list(&self) -> RepoResult<Vec<ApiKey>> {
+        self.observe("list", self.inner.list()).await
+    }
+
+    async fn revoke(&self, id: i32) -> RepoResult<()> {
+        self.observe("revoke", self.inner.revoke(id)).await
+    }
+
+    async fn authenticate(&self, key_hash: &str) -> RepoResult<ApiKey> {
+        self.observe("authenticate", self.inner.authenticate(key_hash)).await
+    }
+}
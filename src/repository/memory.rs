@@ -0,0 +1,413 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::{
+    Actor, ApiKeyRepository, Consumed, Credentials, NewApiKey, RateLimitStore, RefreshToken, RepoError, RepoResult,
+    TokenRepository, UserRepository,
+};
+use crate::models::{ApiKey, CreateUser, Role, UpdateUser, User, UserFilter, UserSortField};
+use crate::pagination::{Page, PageRequest, Position};
+use crate::query::{Sort, SortValue};
+use crate::ratelimit::{Bucket, Decision, Policy};
+
+/// Implementación de `UserRepository` en memoria.
+///
+/// Se usa en modo demo (sin base de datos) y en pruebas de los handlers.
+/// Replica las restricciones del esquema SQL: IDs incrementales y email único
+/// entre los usuarios no eliminados.
+#[derive(Default)]
+pub struct InMemoryUserRepository {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    last_id: i32,
+    users: BTreeMap<i32, Record>,
+}
+
+/// Usuario junto con las columnas que no forman parte de `User`.
+struct Record {
+    user: User,
+    password_hash: Option<String>,
+}
+
+impl State {
+    fn email_taken(&self, email: &str, except: Option<i32>) -> bool {
+        self.users
+            .values()
+            .any(|r| r.is_active() && r.user.email == email && Some(r.user.id) != except)
+    }
+
+    /// Usuario no eliminado con ese ID.
+    fn active_mut(&mut self, id: i32) -> RepoResult<&mut Record> {
+        self.users.get_mut(&id).filter(|r| r.is_active()).ok_or(RepoError::NotFound)
+    }
+}
+
+impl Record {
+    fn is_active(&self) -> bool {
+        self.user.deleted_at.is_none()
+    }
+
+    /// Anota una modificación, como hacen las consultas `UPDATE`.
+    fn touch(&mut self, actor: Actor) {
+        self.user.updated_at = Utc::now();
+        self.user.updated_by = actor.user_id();
+        self.user.updated_by_api_key = actor.api_key_id();
+    }
+
+    /// Equivalente en memoria de los filtros SQL.
+    ///
+    /// La búsqueda de texto es una aproximación: cada término debe coincidir
+    /// con una palabra del nombre o con el email completo.
+    fn matches(&self, filter: &UserFilter) -> bool {
+        let user = &self.user;
+
+        if !filter.include_deleted && !self.is_active() {
+            return false;
+        }
+        if let Some(text) = &filter.name_contains
+            && !user.name.to_lowercase().contains(&text.to_lowercase())
+        {
+            return false;
+        }
+        if let Some(domain) = &filter.email_domain
+            && !user.email.split('@').nth(1).is_some_and(|d| d.eq_ignore_ascii_case(domain))
+        {
+            return false;
+        }
+        if let Some(after) = filter.created_after
+            && user.created_at <= after
+        {
+            return false;
+        }
+        if let Some(after) = filter.updated_after
+            && user.updated_at <= after
+        {
+            return false;
+        }
+        if let Some(search) = &filter.search {
+            let email = user.email.to_lowercase();
+            let name = user.name.to_lowercase();
+            let found = search.to_lowercase().split_whitespace().all(|term| {
+                term == email || name.split_whitespace().any(|word| word == term)
+            });
+            if !found {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn sort_key(&self, sort: &Sort<UserSortField>) -> Vec<SortValue> {
+        sort.keys()
+            .iter()
+            .map(|key| key.field.value(&self.user))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryUserRepository {
+    async fn list(
+        &self,
+        filter: &UserFilter,
+        sort: &Sort<UserSortField>,
+        page: &PageRequest,
+    ) -> RepoResult<Page<User>> {
+        let state = self.state.lock().unwrap();
+        let limit = page.limit as usize;
+
+        let mut rows: Vec<(Vec<SortValue>, &Record)> = state
+            .users
+            .values()
+            .filter(|r| r.matches(filter))
+            .map(|r| (r.sort_key(sort), r))
+            .collect();
+        rows.sort_by(|a, b| sort.compare(&a.0, &b.0));
+        let total = rows.len() as i64;
+
+        let rows: Vec<_> = match &page.position {
+            Position::Offset(offset) => rows.into_iter().skip(*offset as usize).take(limit + 1).collect(),
+            Position::After(key) => rows
+                .into_iter()
+                .filter(|(k, _)| sort.compare(k, key) == Ordering::Greater)
+                .take(limit + 1)
+                .collect(),
+            // Hacia atrás se recorre en orden inverso y `Page::from_rows` lo deshace
+            Position::Before(key) => rows
+                .into_iter()
+                .rev()
+                .filter(|(k, _)| sort.compare(k, key) == Ordering::Less)
+                .take(limit + 1)
+                .collect(),
+        };
+
+        Ok(Page::from_rows(rows, page, total, |(key, _)| key.clone(), |(_, r)| r.user.clone()))
+    }
+
+    async fn get(&self, id: i32) -> RepoResult<User> {
+        let state = self.state.lock().unwrap();
+        state
+            .users
+            .get(&id)
+            .filter(|r| r.is_active())
+            .map(|r| r.user.clone())
+            .ok_or(RepoError::NotFound)
+    }
+
+    async fn get_any(&self, id: i32) -> RepoResult<User> {
+        let state = self.state.lock().unwrap();
+        state.users.get(&id).map(|r| r.user.clone()).ok_or(RepoError::NotFound)
+    }
+
+    async fn credentials(&self, email: &str) -> RepoResult<Credentials> {
+        let state = self.state.lock().unwrap();
+        state
+            .users
+            .values()
+            .find(|r| r.is_active() && r.user.email == email)
+            .map(|r| Credentials { user: r.user.clone(), password_hash: r.password_hash.clone() })
+            .ok_or(RepoError::NotFound)
+    }
+
+    async fn create(&self, user: &CreateUser, password_hash: Option<&str>, actor: Actor) -> RepoResult<User> {
+        let mut state = self.state.lock().unwrap();
+        if state.email_taken(&user.email, None) {
+            return Err(RepoError::Duplicate);
+        }
+
+        state.last_id += 1;
+        let now = Utc::now();
+        let user = User {
+            id: state.last_id,
+            name: user.name.clone(),
+            email: user.email.clone(),
+            role: Role::default(),
+            created_at: now,
+            updated_at: now,
+            created_by: actor.user_id(),
+            updated_by: actor.user_id(),
+            created_by_api_key: actor.api_key_id(),
+            updated_by_api_key: actor.api_key_id(),
+            deleted_at: None,
+        };
+        state.users.insert(user.id, Record {
+            user: user.clone(),
+            password_hash: password_hash.map(str::to_string),
+        });
+
+        Ok(user)
+    }
+
+    async fn update(
+        &self,
+        id: i32,
+        user: &CreateUser,
+        password_hash: Option<&str>,
+        actor: Actor,
+    ) -> RepoResult<User> {
+        let changes = UpdateUser {
+            name: Some(user.name.clone()),
+            email: Some(user.email.clone()),
+            password: None,
+        };
+        self.patch(id, &changes, password_hash, actor).await
+    }
+
+    async fn patch(
+        &self,
+        id: i32,
+        changes: &UpdateUser,
+        password_hash: Option<&str>,
+        actor: Actor,
+    ) -> RepoResult<User> {
+        let mut state = self.state.lock().unwrap();
+        state.active_mut(id)?;
+        if let Some(email) = &changes.email
+            && state.email_taken(email, Some(id))
+        {
+            return Err(RepoError::Duplicate);
+        }
+
+        let record = state.active_mut(id)?;
+        if let Some(name) = &changes.name {
+            record.user.name = name.clone();
+        }
+        if let Some(email) = &changes.email {
+            record.user.email = email.clone();
+        }
+        if let Some(hash) = password_hash {
+            record.password_hash = Some(hash.to_string());
+        }
+        record.touch(actor);
+
+        Ok(record.user.clone())
+    }
+
+    async fn set_role(&self, id: i32, role: Role) -> RepoResult<User> {
+        let mut state = self.state.lock().unwrap();
+        let record = state.active_mut(id)?;
+        record.user.role = role;
+        record.touch(Actor::System);
+        Ok(record.user.clone())
+    }
+
+    async fn delete(&self, id: i32, actor: Actor) -> RepoResult<()> {
+        let mut state = self.state.lock().unwrap();
+        let record = state.active_mut(id)?;
+        record.touch(actor);
+        record.user.deleted_at = Some(record.user.updated_at);
+        Ok(())
+    }
+
+    async fn restore(&self, id: i32, actor: Actor) -> RepoResult<User> {
+        let mut state = self.state.lock().unwrap();
+        let email = state.users.get(&id).map(|r| r.user.email.clone()).ok_or(RepoError::NotFound)?;
+        if state.email_taken(&email, Some(id)) {
+            return Err(RepoError::Duplicate);
+        }
+
+        let record = state.users.get_mut(&id).ok_or(RepoError::NotFound)?;
+        if record.user.deleted_at.take().is_some() {
+            record.touch(actor);
+        }
+        Ok(record.user.clone())
+    }
+
+    async fn purge(&self, before: DateTime<Utc>) -> RepoResult<u64> {
+        let mut state = self.state.lock().unwrap();
+        let count = state.users.len();
+        state.users.retain(|_, r| r.user.deleted_at.is_none_or(|at| at >= before));
+        Ok((count - state.users.len()) as u64)
+    }
+}
+
+/// Implementación de `TokenRepository` en memoria, para el modo demo.
+#[derive(Default)]
+pub struct InMemoryTokenRepository {
+    /// Tokens por hash, con la marca de revocado
+    tokens: Mutex<HashMap<String, (RefreshToken, bool)>>,
+}
+
+#[async_trait]
+impl TokenRepository for InMemoryTokenRepository {
+    async fn insert(&self, token_hash: &str, token: &RefreshToken) -> RepoResult<()> {
+        let mut tokens = self.tokens.lock().unwrap();
+        if tokens.contains_key(token_hash) {
+            return Err(RepoError::Duplicate);
+        }
+        tokens.insert(token_hash.to_string(), (token.clone(), false));
+        Ok(())
+    }
+
+    async fn consume(&self, token_hash: &str) -> RepoResult<Consumed> {
+        let mut tokens = self.tokens.lock().unwrap();
+        let (token, revoked) = tokens.get_mut(token_hash).ok_or(RepoError::NotFound)?;
+        if *revoked {
+            return Ok(Consumed::Reused(token.clone()));
+        }
+        *revoked = true;
+        Ok(Consumed::Valid(token.clone()))
+    }
+
+    async fn revoke_family(&self, family: &str) -> RepoResult<()> {
+        let mut tokens = self.tokens.lock().unwrap();
+        for (token, revoked) in tokens.values_mut() {
+            if token.family == family {
+                *revoked = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Implementación de `ApiKeyRepository` en memoria, para el modo demo.
+#[derive(Default)]
+pub struct InMemoryApiKeyRepository {
+    /// Claves por ID, con el hash de la clave
+    keys: Mutex<BTreeMap<i32, (ApiKey, String)>>,
+}
+
+#[async_trait]
+impl ApiKeyRepository for InMemoryApiKeyRepository {
+    async fn create(&self, key: &NewApiKey<'_>) -> RepoResult<ApiKey> {
+        let mut keys = self.keys.lock().unwrap();
+        if keys.values().any(|(_, hash)| hash == key.key_hash) {
+            return Err(RepoError::Duplicate);
+        }
+
+        let api_key = ApiKey {
+            id: keys.keys().next_back().map_or(1, |id| id + 1),
+            name: key.name.to_string(),
+            prefix: key.prefix.to_string(),
+            scopes: key.scopes.to_vec(),
+            created_at: Utc::now(),
+            expires_at: key.expires_at,
+            last_used_at: None,
+            revoked_at: None,
+        };
+        keys.insert(api_key.id, (api_key.clone(), key.key_hash.to_string()));
+
+        Ok(api_key)
+    }
+
+    async fn list(&self) -> RepoResult<Vec<ApiKey>> {
+        let keys = self.keys.lock().unwrap();
+        Ok(keys.values().map(|(key, _)| key.clone()).collect())
+    }
+
+    async fn revoke(&self, id: i32) -> RepoResult<()> {
+        let mut keys = self.keys.lock().unwrap();
+        match keys.get_mut(&id) {
+            Some((key, _)) if key.revoked_at.is_none() => {
+                key.revoked_at = Some(Utc::now());
+                Ok(())
+            }
+            _ => Err(RepoError::NotFound),
+        }
+    }
+
+    async fn authenticate(&self, key_hash: &str) -> RepoResult<ApiKey> {
+        let mut keys = self.keys.lock().unwrap();
+        let now = Utc::now();
+        let (key, _) = keys
+            .values_mut()
+            .find(|(key, hash)| {
+                hash == key_hash && key.revoked_at.is_none() && key.expires_at.is_none_or(|at| at > now)
+            })
+            .ok_or(RepoError::NotFound)?;
+
+        key.last_used_at = Some(now);
+        Ok(key.clone())
+    }
+}
+
+/// Implementación de `RateLimitStore` en memoria: los límites son por instancia.
+#[derive(Default)]
+pub struct InMemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[async_trait]
+impl RateLimitStore for InMemoryRateLimitStore {
+    async fn acquire(&self, key: &str, policy: &Policy) -> RepoResult<Decision> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let (bucket, decision) = policy.take(buckets.get(key).copied(), Utc::now());
+        buckets.insert(key.to_string(), bucket);
+        Ok(decision)
+    }
+
+    async fn purge(&self, before: DateTime<Utc>) -> RepoResult<u64> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let count = buckets.len();
+        buckets.retain(|_, bucket| bucket.updated_at >= before);
+        Ok((count - buckets.len()) as u64)
+    }
+}
@@ -0,0 +1,209 @@
+mod instrumented;
+mod memory;
+mod postgres;
+
+pub use instrumented::Instrumented;
+pub use memory::{InMemoryApiKeyRepository, InMemoryRateLimitStore, InMemoryTokenRepository, InMemoryUserRepository};
+pub use postgres::{PgApiKeyRepository, PgRateLimitStore, PgTokenRepository, PgUserRepository};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use derive_more::{Display, Error};
+
+use crate::models::{ApiKey, CreateUser, Role, Scope, UpdateUser, User, UserFilter, UserSortField};
+use crate::pagination::{Page, PageRequest};
+use crate::query::Sort;
+use crate::ratelimit::{Decision, Policy};
+
+/// Errores que puede devolver una implementación de almacenamiento.
+#[derive(Debug, Display, Error)]
+pub enum RepoError {
+    /// El registro solicitado no existe.
+    #[display(fmt = "registro no encontrado")]
+    NotFound,
+    /// Se violó una restricción de unicidad (p. ej. email duplicado).
+    #[display(fmt = "registro duplicado")]
+    Duplicate,
+    /// Error inesperado del motor de base de datos.
+    #[display(fmt = "{}", _0)]
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for RepoError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => Self::NotFound,
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => Self::Duplicate,
+            err => Self::Database(err),
+        }
+    }
+}
+
+pub type RepoResult<T> = Result<T, RepoError>;
+
+/// Usuario junto con su hash de contraseña, solo para autenticarlo.
+pub struct Credentials {
+    pub user: User,
+    pub password_hash: Option<String>,
+}
+
+/// Refresh token guardado (del token solo se conoce su hash).
+#[derive(Debug, Clone)]
+pub struct RefreshToken {
+    pub user_id: i32,
+    /// Sesión a la que pertenece; se conserva al rotar
+    pub family: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Resultado de consumir un refresh token.
+pub enum Consumed {
+    /// El token estaba vigente y queda revocado.
+    Valid(RefreshToken),
+    /// El token ya se había usado o revocado: posible robo.
+    Reused(RefreshToken),
+}
+
+/// Datos de una API key nueva; la clave en claro no llega al repositorio.
+pub struct NewApiKey<'a> {
+    pub name: &'a str,
+    pub prefix: &'a str,
+    pub key_hash: &'a str,
+    pub scopes: &'a [Scope],
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Administrador que la crea
+    pub created_by: Option<i32>,
+}
+
+/// Quién hace un cambio, para los metadatos de auditoría de `users`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Actor {
+    /// Usuario autenticado con un JWT: `created_by`/`updated_by`
+    User(i32),
+    /// API key: `created_by_api_key`/`updated_by_api_key`
+    ApiKey(i32),
+    /// El propio servidor, p. ej. al crear el administrador inicial
+    System,
+}
+
+impl Actor {
+    pub fn user_id(self) -> Option<i32> {
+        match self {
+            Self::User(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn api_key_id(self) -> Option<i32> {
+        match self {
+            Self::ApiKey(id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+/// Operaciones de persistencia sobre usuarios que usan los handlers de `/users`.
+///
+/// Las escrituras actualizan `updated_at` y anotan a `actor` como autor del cambio.
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    /// Devuelve una página de los usuarios que cumplen `filter`, en el orden `sort`.
+    async fn list(
+        &self,
+        filter: &UserFilter,
+        sort: &Sort<UserSortField>,
+        page: &PageRequest,
+    ) -> RepoResult<Page<User>>;
+
+    /// Busca un usuario por su ID; los eliminados no se encuentran.
+    async fn get(&self, id: i32) -> RepoResult<User>;
+
+    /// Busca un usuario por su ID, aunque esté eliminado.
+    async fn get_any(&self, id: i32) -> RepoResult<User>;
+
+    /// Busca un usuario no eliminado por email junto con su hash de contraseña.
+    async fn credentials(&self, email: &str) -> RepoResult<Credentials>;
+
+    /// Crea un usuario nuevo y lo devuelve con su ID asignado.
+    ///
+    /// `password_hash` ya viene calculado; la contraseña en claro de `user` se ignora.
+    async fn create(&self, user: &CreateUser, password_hash: Option<&str>, actor: Actor) -> RepoResult<User>;
+
+    /// Reemplaza el nombre y email de un usuario existente.
+    ///
+    /// Esta operación y las siguientes devuelven `NotFound` si el usuario está eliminado.
+    ///
+    /// Sin `password_hash` se conserva la contraseña actual.
+    async fn update(
+        &self,
+        id: i32,
+        user: &CreateUser,
+        password_hash: Option<&str>,
+        actor: Actor,
+    ) -> RepoResult<User>;
+
+    /// Actualiza solo los campos presentes en `changes` (y la contraseña si hay `password_hash`).
+    async fn patch(
+        &self,
+        id: i32,
+        changes: &UpdateUser,
+        password_hash: Option<&str>,
+        actor: Actor,
+    ) -> RepoResult<User>;
+
+    /// Cambia el rol de un usuario; solo lo hace el servidor al arrancar.
+    async fn set_role(&self, id: i32, role: Role) -> RepoResult<User>;
+
+    /// Marca un usuario como eliminado; la fila se conserva hasta la purga.
+    async fn delete(&self, id: i32, actor: Actor) -> RepoResult<()>;
+
+    /// Recupera un usuario eliminado; si no lo estaba, lo devuelve sin cambios.
+    ///
+    /// `Duplicate` si otro usuario ha registrado su email entretanto.
+    async fn restore(&self, id: i32, actor: Actor) -> RepoResult<User>;
+
+    /// Borra definitivamente los usuarios eliminados antes de `before` y devuelve cuántos había.
+    async fn purge(&self, before: DateTime<Utc>) -> RepoResult<u64>;
+}
+
+/// Almacenamiento de los refresh tokens emitidos por `/auth`.
+#[async_trait]
+pub trait TokenRepository: Send + Sync {
+    /// Guarda un token nuevo identificado por el hash `token_hash`.
+    async fn insert(&self, token_hash: &str, token: &RefreshToken) -> RepoResult<()>;
+
+    /// Marca el token como usado de forma atómica y lo devuelve.
+    ///
+    /// `NotFound` si el hash no corresponde a ningún token.
+    async fn consume(&self, token_hash: &str) -> RepoResult<Consumed>;
+
+    /// Revoca todos los tokens de una sesión.
+    async fn revoke_family(&self, family: &str) -> RepoResult<()>;
+}
+
+/// Almacenamiento de las API keys de `/api-keys`.
+#[async_trait]
+pub trait ApiKeyRepository: Send + Sync {
+    async fn create(&self, key: &NewApiKey<'_>) -> RepoResult<ApiKey>;
+
+    /// Todas las claves, incluidas las revocadas y caducadas, por ID.
+    async fn list(&self) -> RepoResult<Vec<ApiKey>>;
+
+    /// Marca la clave como revocada; `NotFound` si no existe o ya estaba revocada.
+    async fn revoke(&self, id: i32) -> RepoResult<()>;
+
+    /// Busca una clave vigente por su hash y anota el uso en `last_used_at`.
+    ///
+    /// `NotFound` si no existe, está revocada o ha caducado.
+    async fn authenticate(&self, key_hash: &str) -> RepoResult<ApiKey>;
+}
+
+/// Almacén de los token buckets del rate limiter.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Repone y consume un token del bucket `key` según `policy`, de forma atómica.
+    async fn acquire(&self, key: &str, policy: &Policy) -> RepoResult<Decision>;
+
+    /// Elimina los buckets sin uso desde `before` y devuelve cuántos había.
+    async fn purge(&self, before: DateTime<Utc>) -> RepoResult<u64>;
+}
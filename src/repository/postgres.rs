@@ -0,0 +1,502 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+
+use super::{
+    Actor, ApiKeyRepository, Consumed, Credentials, NewApiKey, RateLimitStore, RefreshToken, RepoError, RepoResult,
+    TokenRepository, UserRepository,
+};
+use crate::models::{ApiKey, CreateUser, Role, Scope, UpdateUser, User, UserFilter, UserSortField};
+use crate::pagination::{Page, PageRequest, Position};
+use crate::query::{escape_like, Sort, WhereClause};
+use crate::ratelimit::{Bucket, Decision, Policy};
+
+/// Columnas de `users` que forman `User`.
+const USER_COLUMNS: &str =
+    "id, name, email, role, created_at, updated_at, created_by, updated_by, created_by_api_key, \
+     updated_by_api_key, deleted_at";
+
+/// Fila de `users` con el hash de la contraseña, solo para `/auth/login`.
+#[derive(FromRow)]
+struct CredentialsRow {
+    #[sqlx(flatten)]
+    user: User,
+    password_hash: Option<String>,
+}
+
+/// Añade las condiciones de `filter` a la consulta.
+fn push_filters(query: &mut QueryBuilder<'_, Postgres>, clause: &mut WhereClause, filter: &UserFilter) {
+    if !filter.include_deleted {
+        clause.and(query).push("deleted_at IS NULL");
+    }
+    if let Some(text) = &filter.name_contains {
+        clause
+            .and(query)
+            .push("name ILIKE ")
+            .push_bind(format!("%{}%", escape_like(text)));
+    }
+    if let Some(domain) = &filter.email_domain {
+        clause
+            .and(query)
+            .push("lower(split_part(email, '@', 2)) = lower(")
+            .push_bind(domain.clone())
+            .push(")");
+    }
+    if let Some(after) = filter.created_after {
+        clause.and(query).push("created_at > ").push_bind(after);
+    }
+    if let Some(after) = filter.updated_after {
+        clause.and(query).push("updated_at > ").push_bind(after);
+    }
+    if let Some(search) = &filter.search {
+        // Usa el índice GIN `users_search_idx`; la expresión debe coincidir con la del índice
+        clause
+            .and(query)
+            .push("to_tsvector('simple', name || ' ' || email) @@ websearch_to_tsquery('simple', ")
+            .push_bind(search.clone())
+            .push(")");
+    }
+}
+
+/// Implementación de `UserRepository` sobre PostgreSQL.
+pub struct PgUserRepository {
+    pool: PgPool,
+}
+
+impl PgUserRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UserRepository for PgUserRepository {
+    async fn list(
+        &self,
+        filter: &UserFilter,
+        sort: &Sort<UserSortField>,
+        page: &PageRequest,
+    ) -> RepoResult<Page<User>> {
+        let mut count: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(*) FROM users");
+        push_filters(&mut count, &mut WhereClause::default(), filter);
+        let total: i64 = count.build_query_scalar().fetch_one(&self.pool).await?;
+
+        let mut query: QueryBuilder<Postgres> =
+            QueryBuilder::new(format!("SELECT {} FROM users", USER_COLUMNS));
+        let mut clause = WhereClause::default();
+        push_filters(&mut query, &mut clause, filter);
+
+        // Hacia atrás se recorre en orden inverso y `Page::from_rows` lo deshace
+        let reverse = matches!(page.position, Position::Before(_));
+        if let Position::After(key) | Position::Before(key) = &page.position {
+            sort.push_keyset(clause.and(&mut query), key, reverse);
+        }
+        sort.push_order_by(&mut query, reverse);
+
+        // Se pide un elemento extra para saber si hay más páginas
+        query.push(" LIMIT ").push_bind(i64::from(page.limit) + 1);
+        if let Position::Offset(offset) = page.position {
+            query.push(" OFFSET ").push_bind(offset as i64);
+        }
+
+        let rows = query.build_query_as::<User>().fetch_all(&self.pool).await?;
+
+        Ok(Page::from_rows(
+            rows,
+            page,
+            total,
+            |user| sort.keys().iter().map(|k| k.field.value(user)).collect(),
+            |user| user,
+        ))
+    }
+
+    async fn get(&self, id: i32) -> RepoResult<User> {
+        let user = sqlx::query_as::<_, User>(&format!(
+            "SELECT {} FROM users WHERE id = $1 AND deleted_at IS NULL",
+            USER_COLUMNS
+        ))
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn get_any(&self, id: i32) -> RepoResult<User> {
+        let user = sqlx::query_as::<_, User>(&format!("SELECT {} FROM users WHERE id = $1", USER_COLUMNS))
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(user)
+    }
+
+    async fn credentials(&self, email: &str) -> RepoResult<Credentials> {
+        let row = sqlx::query_as::<_, CredentialsRow>(&format!(
+            "SELECT {}, password_hash FROM users WHERE email = $1 AND deleted_at IS NULL",
+            USER_COLUMNS
+        ))
+        .bind(email)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Credentials { user: row.user, password_hash: row.password_hash })
+    }
+
+    async fn create(&self, user: &CreateUser, password_hash: Option<&str>, actor: Actor) -> RepoResult<User> {
+        let user = sqlx::query_as::<_, User>(&format!(
+            "INSERT INTO users (name, email, password_hash, created_by, updated_by, \
+             created_by_api_key, updated_by_api_key) VALUES ($1, $2, $3, $4, $4, $5, $5) RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(&user.name)
+        .bind(&user.email)
+        .bind(password_hash)
+        .bind(actor.user_id())
+        .bind(actor.api_key_id())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn update(
+        &self,
+        id: i32,
+        user: &CreateUser,
+        password_hash: Option<&str>,
+        actor: Actor,
+    ) -> RepoResult<User> {
+        let user = sqlx::query_as::<_, User>(&format!(
+            "UPDATE users SET name = $1, email = $2, password_hash = COALESCE($3, password_hash), \
+             updated_at = now(), updated_by = $4, updated_by_api_key = $5 \
+             WHERE id = $6 AND deleted_at IS NULL RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(&user.name)
+        .bind(&user.email)
+        .bind(password_hash)
+        .bind(actor.user_id())
+        .bind(actor.api_key_id())
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn patch(
+        &self,
+        id: i32,
+        changes: &UpdateUser,
+        password_hash: Option<&str>,
+        actor: Actor,
+    ) -> RepoResult<User> {
+        // Los campos en NULL conservan su valor actual
+        let user = sqlx::query_as::<_, User>(&format!(
+            "UPDATE users SET name = COALESCE($1, name), email = COALESCE($2, email), \
+             password_hash = COALESCE($3, password_hash), updated_at = now(), \
+             updated_by = $4, updated_by_api_key = $5 \
+             WHERE id = $6 AND deleted_at IS NULL RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(&changes.name)
+        .bind(&changes.email)
+        .bind(password_hash)
+        .bind(actor.user_id())
+        .bind(actor.api_key_id())
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn set_role(&self, id: i32, role: Role) -> RepoResult<User> {
+        let user = sqlx::query_as::<_, User>(&format!(
+            "UPDATE users SET role = $1, updated_at = now(), updated_by = NULL, updated_by_api_key = NULL \
+             WHERE id = $2 AND deleted_at IS NULL RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(role)
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn delete(&self, id: i32, actor: Actor) -> RepoResult<()> {
+        let result = sqlx::query(
+            "UPDATE users SET deleted_at = now(), updated_at = now(), updated_by = $1, updated_by_api_key = $2 \
+             WHERE id = $3 AND deleted_at IS NULL"
+        )
+        .bind(actor.user_id())
+        .bind(actor.api_key_id())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        // Ninguna fila afectada: el usuario no existía o ya estaba eliminado
+        if result.rows_affected() == 0 {
+            return Err(RepoError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn restore(&self, id: i32, actor: Actor) -> RepoResult<User> {
+        // El índice único parcial de `email` rechaza la fila si otro usuario activo lo usa.
+        // Un usuario que no estaba eliminado se devuelve sin tocar sus metadatos
+        let user = sqlx::query_as::<_, User>(&format!(
+            "UPDATE users SET deleted_at = NULL, \
+             updated_at = CASE WHEN deleted_at IS NULL THEN updated_at ELSE now() END, \
+             updated_by = CASE WHEN deleted_at IS NULL THEN updated_by ELSE $1 END, \
+             updated_by_api_key = CASE WHEN deleted_at IS NULL THEN updated_by_api_key ELSE $2 END \
+             WHERE id = $3 RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(actor.user_id())
+        .bind(actor.api_key_id())
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn purge(&self, before: DateTime<Utc>) -> RepoResult<u64> {
+        let result = sqlx::query("DELETE FROM users WHERE deleted_at < $1")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Implementación de `TokenRepository` sobre la tabla `refresh_tokens`.
+pub struct PgTokenRepository {
+    pool: PgPool,
+}
+
+impl PgTokenRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[derive(FromRow)]
+struct RefreshTokenRow {
+    user_id: i32,
+    family: String,
+    expires_at: DateTime<Utc>,
+}
+
+impl From<RefreshTokenRow> for RefreshToken {
+    fn from(row: RefreshTokenRow) -> Self {
+        Self { user_id: row.user_id, family: row.family, expires_at: row.expires_at }
+    }
+}
+
+#[async_trait]
+impl TokenRepository for PgTokenRepository {
+    async fn insert(&self, token_hash: &str, token: &RefreshToken) -> RepoResult<()> {
+        sqlx::query("INSERT INTO refresh_tokens (user_id, token_hash, family, expires_at) VALUES ($1, $2, $3, $4)")
+            .bind(token.user_id)
+            .bind(token_hash)
+            .bind(&token.family)
+            .bind(token.expires_at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn consume(&self, token_hash: &str) -> RepoResult<Consumed> {
+        // El UPDATE es atómico: de dos peticiones concurrentes con el mismo token solo una lo consume
+        let consumed = sqlx::query_as::<_, RefreshTokenRow>(
+            "UPDATE refresh_tokens SET revoked_at = now() \
+             WHERE token_hash = $1 AND revoked_at IS NULL \
+             RETURNING user_id, family, expires_at"
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(row) = consumed {
+            return Ok(Consumed::Valid(row.into()));
+        }
+
+        let reused = sqlx::query_as::<_, RefreshTokenRow>(
+            "SELECT user_id, family, expires_at FROM refresh_tokens WHERE token_hash = $1"
+        )
+        .bind(token_hash)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Consumed::Reused(reused.into()))
+    }
+
+    async fn revoke_family(&self, family: &str) -> RepoResult<()> {
+        sqlx::query("UPDATE refresh_tokens SET revoked_at = now() WHERE family = $1 AND revoked_at IS NULL")
+            .bind(family)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Implementación de `ApiKeyRepository` sobre la tabla `api_keys`.
+pub struct PgApiKeyRepository {
+    pool: PgPool,
+}
+
+impl PgApiKeyRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// Columnas de `api_keys` que forman `ApiKey`.
+const API_KEY_COLUMNS: &str = "id, name, prefix, scopes, created_at, expires_at, last_used_at, revoked_at";
+
+/// Fila de `api_keys`; los scopes se guardan como `TEXT[]`.
+#[derive(FromRow)]
+struct ApiKeyRow {
+    id: i32,
+    name: String,
+    prefix: String,
+    scopes: Vec<String>,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    last_used_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKeyRow> for ApiKey {
+    fn from(row: ApiKeyRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            prefix: row.prefix,
+            // El CHECK de la tabla solo admite scopes conocidos
+            scopes: row.scopes.iter().filter_map(|scope| Scope::parse(scope)).collect(),
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+            last_used_at: row.last_used_at,
+            revoked_at: row.revoked_at,
+        }
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for PgApiKeyRepository {
+    async fn create(&self, key: &NewApiKey<'_>) -> RepoResult<ApiKey> {
+        let scopes: Vec<&str> = key.scopes.iter().map(|scope| scope.as_str()).collect();
+        let row = sqlx::query_as::<_, ApiKeyRow>(&format!(
+            "INSERT INTO api_keys (name, prefix, key_hash, scopes, expires_at, created_by) \
+             VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+            API_KEY_COLUMNS
+        ))
+        .bind(key.name)
+        .bind(key.prefix)
+        .bind(key.key_hash)
+        .bind(scopes)
+        .bind(key.expires_at)
+        .bind(key.created_by)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    async fn list(&self) -> RepoResult<Vec<ApiKey>> {
+        let rows = sqlx::query_as::<_, ApiKeyRow>(&format!("SELECT {} FROM api_keys ORDER BY id", API_KEY_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(ApiKey::from).collect())
+    }
+
+    async fn revoke(&self, id: i32) -> RepoResult<()> {
+        let result = sqlx::query("UPDATE api_keys SET revoked_at = now() WHERE id = $1 AND revoked_at IS NULL")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepoError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn authenticate(&self, key_hash: &str) -> RepoResult<ApiKey> {
+        let row = sqlx::query_as::<_, ApiKeyRow>(&format!(
+            "UPDATE api_keys SET last_used_at = now() \
+             WHERE key_hash = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > now()) \
+             RETURNING {}",
+            API_KEY_COLUMNS
+        ))
+        .bind(key_hash)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.into())
+    }
+}
+
+/// Implementación de `RateLimitStore` sobre la tabla `rate_limits`, compartida por todas las instancias.
+pub struct PgRateLimitStore {
+    pool: PgPool,
+}
+
+impl PgRateLimitStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for PgRateLimitStore {
+    async fn acquire(&self, key: &str, policy: &Policy) -> RepoResult<Decision> {
+        let mut tx = self.pool.begin().await?;
+
+        // El bloqueo de la fila serializa las peticiones concurrentes del mismo cliente;
+        // se usa la hora de la base de datos para que todas las instancias coincidan
+        let now: DateTime<Utc> = sqlx::query_scalar("SELECT now()").fetch_one(&mut *tx).await?;
+        let row: Option<(f64, DateTime<Utc>)> =
+            sqlx::query_as("SELECT tokens, updated_at FROM rate_limits WHERE key = $1 FOR UPDATE")
+                .bind(key)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+        let previous = row.map(|(tokens, updated_at)| Bucket { tokens, updated_at });
+        let (bucket, decision) = policy.take(previous, now);
+
+        // Si dos peticiones crean el bucket a la vez, la segunda sobrescribe a la primera
+        sqlx::query(
+            "INSERT INTO rate_limits (key, tokens, updated_at) VALUES ($1, $2, $3) \
+             ON CONFLICT (key) DO UPDATE SET tokens = EXCLUDED.tokens, updated_at = EXCLUDED.updated_at"
+        )
+        .bind(key)
+        .bind(bucket.tokens)
+        .bind(bucket.updated_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(decision)
+    }
+
+    async fn purge(&self, before: DateTime<Utc>) -> RepoResult<u64> {
+        let result = sqlx::query("DELETE FROM rate_limits WHERE updated_at < $1")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
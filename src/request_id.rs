@@ -0,0 +1,78 @@
+use std::fmt::Write;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    Error,
+};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+/// Cabecera con el identificador de la petición.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longitud máxima de un identificador recibido del cliente o de un proxy.
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    /// Identificador de la petición que se está atendiendo en la tarea actual.
+    static CURRENT: RequestId;
+}
+
+/// Identificador de una petición.
+#[derive(Debug, Clone)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Usa el identificador recibido si es razonable; si no, genera uno nuevo.
+    fn from_header(value: Option<&HeaderValue>) -> Self {
+        match value.and_then(|value| value.to_str().ok()).filter(|value| is_valid(value)) {
+            Some(value) => Self(value.to_string()),
+            None => Self::generate(),
+        }
+    }
+
+    /// 128 bits aleatorios en hexadecimal.
+    fn generate() -> Self {
+        let mut bytes = [0u8; 16];
+        OsRng.fill_bytes(&mut bytes);
+        Self(bytes.iter().fold(String::with_capacity(32), |mut id, byte| {
+            let _ = write!(id, "{:02x}", byte);
+            id
+        }))
+    }
+}
+
+/// Un identificador ajeno acaba en logs y cabeceras: se limita su longitud y sus caracteres.
+fn is_valid(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_LEN
+        && value.bytes().all(|byte| byte.is_ascii_alphanumeric() || b"-_.:".contains(&byte))
+}
+
+/// Identificador de la petición en curso, para los logs y los cuerpos de error.
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|id| id.0.clone()).ok()
+}
+
+/// Middleware que acepta el `X-Request-Id` del cliente o genera uno, lo deja disponible
+/// mientras se atiende la petición y lo devuelve en la respuesta.
+///
+/// Debe ser el más externo para que el resto de middlewares ya lo vean.
+pub async fn assign(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let id = RequestId::from_header(req.headers().get(REQUEST_ID_HEADER));
+
+    let mut res = CURRENT.scope(id.clone(), next.call(req)).await?;
+    if let Ok(value) = HeaderValue::from_str(id.as_str()) {
+        res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+    Ok(res)
+}
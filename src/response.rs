@@ -1,40 +1,154 @@
 use actix_web::{
-    error,
-    http::{header::ContentType, StatusCode},
+    error::{self, ResponseError},
+    http::{header::{ContentType, CONTENT_LANGUAGE, WWW_AUTHENTICATE}, StatusCode},
     web, HttpResponse, Result,
 };
 use derive_more::{Display, Error}; // Para implementar automáticamente `Display` y `Error`
 use log::warn;
 use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::models::{ApiKey, User};
+use crate::pagination::PageInfo;
+use crate::problem::ProblemDetails;
+use crate::repository::RepoError;
+use crate::request_id;
+use crate::i18n::{self, Locale};
+use crate::validation::{self, FieldErrors, FieldMessages};
 
 
 /// Tipo de resultado estándar usado por los controladores (handlers).
 pub type AppResult<T> = actix_web::Result<web::Json<OkModel<T>>, AppError>;
 
 /// Modelo de respuesta para errores.
-#[derive(Serialize)]
+///
+/// Es el formato por defecto; con `Accept: application/problem+json` se responde
+/// con `ProblemDetails` (ver `problem::render_errors`).
+#[derive(Serialize, ToSchema)]
 pub struct ErrModel {
+    #[schema(example = false)]
     pub success: bool,
-    pub err: &'static str,
+    /// Código estable para que los clientes distingan el error sin depender del mensaje
+    pub code: ErrorCode,
+    #[schema(example = "Usuario 42 no encontrado")]
+    pub err: String,
+    /// Información adicional del error, p. ej. el valor rechazado
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<Value>,
+    /// Errores de validación por campo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<HashMap<String, Vec<String>>>)]
+    pub errors: Option<FieldMessages>,
+    /// Identificador de la petición (cabecera `X-Request-Id`), para localizarla en los logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "4f1c2e8a9b7d4c3e8f0a1b2c3d4e5f60")]
+    pub request_id: Option<String>,
 }
 
 /// Modelo de respuesta para éxitos.
-#[derive(Serialize)]
+///
+/// Los listados paginados incluyen además el bloque `pagination`.
+#[derive(Serialize, ToSchema)]
+#[aliases(UserPage = OkModel<Vec<User>>, ApiKeyList = OkModel<Vec<ApiKey>>)]
 pub struct OkModel<T>
 where
     T: Serialize,
 {
     pub success: bool,
     pub data: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pagination: Option<PageInfo>,
+}
+
+/// Código de error legible por máquinas que acompaña al mensaje.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// Petición mal formada sin un código más específico
+    BadRequest,
+    /// Parámetros de consulta que no se pudieron interpretar
+    InvalidQuery,
+    /// Cuerpo JSON que no se pudo interpretar
+    InvalidBody,
+    /// Cursor de paginación corrupto o de otro orden
+    InvalidCursor,
+    /// Campo de ordenación no permitido o repetido
+    InvalidSort,
+    /// Documento de parche mal formado
+    InvalidPatch,
+    /// Content-Type no soportado
+    UnsupportedMediaType,
+    /// Faltan campos obligatorios
+    MissingFields,
+    /// Uno o más campos no cumplen sus restricciones
+    ValidationFailed,
+    /// Falta el token o no se pudo autenticar
+    Unauthorized,
+    /// Token mal formado, con firma inválida o de otro emisor
+    InvalidToken,
+    /// Token caducado
+    TokenExpired,
+    /// Email o contraseña incorrectos
+    InvalidCredentials,
+    /// Refresh token desconocido, caducado o ya usado
+    InvalidRefreshToken,
+    /// API key desconocida, revocada o caducada
+    InvalidApiKey,
+    Forbidden,
+    /// Recurso genérico no encontrado
+    NotFound,
+    /// API key inexistente o ya revocada
+    ApiKeyNotFound,
+    UserNotFound,
+    /// Conflicto genérico con el estado del recurso
+    Conflict,
+    EmailTaken,
+    /// El parche no se pudo aplicar sobre el recurso
+    PatchFailed,
+    /// Se intentó modificar un campo de solo lectura
+    ImmutableField,
+    RateLimited,
+    InternalError,
+    DatabaseUnavailable,
 }
 
 /// `AppError` representa los errores que pueden ocurrir en la aplicación.
+///
+/// Cada variante fija el código HTTP; `code` y `err` describen el error concreto
+/// y `details` puede añadir datos estructurados (se construyen con `AppError::invalid`, etc.).
 #[derive(Debug, Display, Error, Serialize)]
 pub enum AppError {
     /// Error por solicitud inválida (400)
-    Invalid { err: &'static str },
+    #[display(fmt = "{}", err)]
+    Invalid { code: ErrorCode, err: String, details: Option<Value> },
+    /// Uno o más campos no cumplen sus restricciones (400)
+    #[display(fmt = "Datos de entrada inválidos")]
+    Validation { errors: FieldErrors },
+    /// Falta autenticación o no es válida (401)
+    #[display(fmt = "{}", err)]
+    Unauthorized { code: ErrorCode, err: String, details: Option<Value> },
+    /// Autenticado pero sin permisos suficientes (403)
+    #[display(fmt = "{}", err)]
+    Forbidden { code: ErrorCode, err: String, details: Option<Value> },
+    /// El recurso no existe (404)
+    #[display(fmt = "{}", err)]
+    NotFound { code: ErrorCode, err: String, details: Option<Value> },
+    /// Conflicto con el estado actual del recurso, p. ej. email duplicado (409)
+    #[display(fmt = "{}", err)]
+    Conflict { code: ErrorCode, err: String, details: Option<Value> },
+    /// Petición bien formada pero imposible de procesar (422)
+    #[display(fmt = "{}", err)]
+    UnprocessableEntity { code: ErrorCode, err: String, details: Option<Value> },
+    /// Se superó el límite de peticiones (429)
+    #[display(fmt = "{}", err)]
+    TooManyRequests { code: ErrorCode, err: String, details: Option<Value> },
     /// Error interno del servidor (500)
     InternalError,
+    /// Una dependencia (p. ej. la base de datos) no está disponible (503)
+    #[display(fmt = "{}", err)]
+    ServiceUnavailable { code: ErrorCode, err: String, details: Option<Value> },
 }
 
 /// Implementación para convertir `AppError` en una respuesta HTTP.
@@ -42,43 +156,217 @@ impl error::ResponseError for AppError {
     /// Devuelve el código de estado HTTP correspondiente al error.
     fn status_code(&self) -> StatusCode {
         match *self {
-            Self::Invalid { .. } => StatusCode::BAD_REQUEST, // 400
+            Self::Invalid { .. } | Self::Validation { .. } => StatusCode::BAD_REQUEST, // 400
+            Self::Unauthorized { .. } => StatusCode::UNAUTHORIZED, // 401
+            Self::Forbidden { .. } => StatusCode::FORBIDDEN, // 403
+            Self::NotFound { .. } => StatusCode::NOT_FOUND, // 404
+            Self::Conflict { .. } => StatusCode::CONFLICT, // 409
+            Self::UnprocessableEntity { .. } => StatusCode::UNPROCESSABLE_ENTITY, // 422
+            Self::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS, // 429
             Self::InternalError => StatusCode::INTERNAL_SERVER_ERROR, // 500
+            Self::ServiceUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE, // 503
         }
     }
 
-    /// Genera la respuesta HTTP correspondiente al error.
+    /// Genera la respuesta HTTP correspondiente al error, en el idioma por defecto.
+    ///
+    /// `problem::render_errors` la sustituye según `Accept` y `Accept-Language`.
     fn error_response(&self) -> HttpResponse {
-        let mut builder = HttpResponse::build(self.status_code());
-        let resp = builder.insert_header(ContentType::json());
+        self.localized_response(Locale::default())
+    }
+}
+
+impl AppError {
+    pub fn invalid(code: ErrorCode, err: impl Into<String>) -> Self {
+        Self::Invalid { code, err: err.into(), details: None }
+    }
+
+    pub fn unauthorized(err: impl Into<String>) -> Self {
+        Self::Unauthorized { code: ErrorCode::Unauthorized, err: err.into(), details: None }
+    }
+
+    pub fn forbidden(err: impl Into<String>) -> Self {
+        Self::Forbidden { code: ErrorCode::Forbidden, err: err.into(), details: None }
+    }
+
+    pub fn not_found(code: ErrorCode, err: impl Into<String>) -> Self {
+        Self::NotFound { code, err: err.into(), details: None }
+    }
+
+    pub fn conflict(code: ErrorCode, err: impl Into<String>) -> Self {
+        Self::Conflict { code, err: err.into(), details: None }
+    }
 
+    pub fn unprocessable(code: ErrorCode, err: impl Into<String>) -> Self {
+        Self::UnprocessableEntity { code, err: err.into(), details: None }
+    }
+
+    pub fn too_many_requests(err: impl Into<String>) -> Self {
+        Self::TooManyRequests { code: ErrorCode::RateLimited, err: err.into(), details: None }
+    }
+
+    pub fn unavailable(code: ErrorCode, err: impl Into<String>) -> Self {
+        Self::ServiceUnavailable { code, err: err.into(), details: None }
+    }
+
+    /// Añade datos estructurados al error (no aplica a `Validation` ni a `InternalError`).
+    pub fn with_details(mut self, value: Value) -> Self {
+        match &mut self {
+            Self::Invalid { details, .. }
+            | Self::Unauthorized { details, .. }
+            | Self::Forbidden { details, .. }
+            | Self::NotFound { details, .. }
+            | Self::Conflict { details, .. }
+            | Self::UnprocessableEntity { details, .. }
+            | Self::TooManyRequests { details, .. }
+            | Self::ServiceUnavailable { details, .. } => *details = Some(value),
+            Self::Validation { .. } | Self::InternalError => {}
+        }
+        self
+    }
+
+    pub fn code(&self) -> ErrorCode {
         match *self {
-            // Error de cliente (400)
-            Self::Invalid { err } => resp.json(ErrModel {
-                success: false,
-                err,
-            }),
+            Self::Invalid { code, .. }
+            | Self::Unauthorized { code, .. }
+            | Self::Forbidden { code, .. }
+            | Self::NotFound { code, .. }
+            | Self::Conflict { code, .. }
+            | Self::UnprocessableEntity { code, .. }
+            | Self::TooManyRequests { code, .. }
+            | Self::ServiceUnavailable { code, .. } => code,
+            Self::Validation { .. } => ErrorCode::ValidationFailed,
+            Self::InternalError => ErrorCode::InternalError,
+        }
+    }
+
+    /// Mensaje con el que se creó el error (en español, también se usa en los logs).
+    ///
+    /// El de los errores 500 es genérico: el detalle solo se registra en los logs.
+    pub fn message(&self) -> &str {
+        match self {
+            // Errores de cliente (4xx) y de disponibilidad (503)
+            Self::Invalid { err, .. }
+            | Self::Unauthorized { err, .. }
+            | Self::Forbidden { err, .. }
+            | Self::NotFound { err, .. }
+            | Self::Conflict { err, .. }
+            | Self::UnprocessableEntity { err, .. }
+            | Self::TooManyRequests { err, .. }
+            | Self::ServiceUnavailable { err, .. } => err,
+            Self::Validation { .. } => "Datos de entrada inválidos",
             // Error de servidor (500), mensaje oculto al cliente
-            Self::InternalError => resp.json(ErrModel {
-                success: false,
-                err: "500 error interno del servidor",
-            }),
+            Self::InternalError => "500 error interno del servidor",
+        }
+    }
+
+    pub fn details(&self) -> Option<&Value> {
+        match self {
+            Self::Invalid { details, .. }
+            | Self::Unauthorized { details, .. }
+            | Self::Forbidden { details, .. }
+            | Self::NotFound { details, .. }
+            | Self::Conflict { details, .. }
+            | Self::UnprocessableEntity { details, .. }
+            | Self::TooManyRequests { details, .. }
+            | Self::ServiceUnavailable { details, .. } => details.as_ref(),
+            Self::Validation { .. } | Self::InternalError => None,
         }
     }
+
+    /// Errores por campo, solo presentes en `Validation`.
+    pub fn field_errors(&self) -> Option<&FieldErrors> {
+        match self {
+            Self::Validation { errors } => Some(errors),
+            _ => None,
+        }
+    }
+
+    /// Mensaje para el cliente en el idioma pedido.
+    ///
+    /// Sale del catálogo por código; si no hay traducción se usa el mensaje original.
+    pub fn localized_message(&self, locale: Locale) -> String {
+        i18n::error_message(self.code(), locale, self.details())
+            .unwrap_or_else(|| self.message().to_string())
+    }
+
+    fn localized_errors(&self, locale: Locale) -> Option<FieldMessages> {
+        self.field_errors().map(|errors| validation::messages(errors, locale))
+    }
+
+    /// Valor de `WWW-Authenticate` para los 401 (RFC 6750).
+    fn challenge(&self) -> Option<&'static str> {
+        match self {
+            Self::Unauthorized { code: ErrorCode::InvalidToken | ErrorCode::TokenExpired, .. } => {
+                Some("Bearer error=\"invalid_token\"")
+            }
+            Self::Unauthorized { code: ErrorCode::Unauthorized, .. } => Some("Bearer"),
+            _ => None,
+        }
+    }
+
+    /// Respuesta con `ErrModel` en el idioma pedido.
+    pub fn localized_response(&self, locale: Locale) -> HttpResponse {
+        let mut builder = HttpResponse::build(self.status_code());
+        builder
+            .insert_header(ContentType::json())
+            .insert_header((CONTENT_LANGUAGE, locale.tag()));
+        if let Some(challenge) = self.challenge() {
+            builder.insert_header((WWW_AUTHENTICATE, challenge));
+        }
+
+        builder.json(ErrModel {
+            success: false,
+            code: self.code(),
+            err: self.localized_message(locale),
+            details: self.details().cloned(),
+            errors: self.localized_errors(locale),
+            request_id: request_id::current(),
+        })
+    }
+
+    /// Representación del error como Problem Details (RFC 7807) en el idioma pedido.
+    pub fn problem(&self, locale: Locale) -> ProblemDetails {
+        let mut problem = ProblemDetails::new(self.status_code(), Some(self.localized_message(locale)));
+        problem.code = Some(self.code());
+        problem.details = self.details().cloned();
+        problem.errors = self.localized_errors(locale);
+        problem
+    }
 }
 
 /// Convierte un error de SQLx en un `AppError` tipo interno
+///
+/// Si no se pudo obtener conexión del pool se responde 503 para que el cliente reintente.
 impl From<sqlx::Error> for AppError {
     fn from(err: sqlx::Error) -> Self {
         warn!("{}", err); // Se registra en logs
-        Self::InternalError
+        match err {
+            sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) => {
+                Self::unavailable(ErrorCode::DatabaseUnavailable, "Base de datos no disponible")
+            }
+            _ => Self::InternalError,
+        }
     }
 }
 
+/// Convierte un error del repositorio en un `AppError`.
+///
+/// Los handlers que necesiten mensajes específicos deben hacer su propio `match`.
+impl From<RepoError> for AppError {
+    fn from(err: RepoError) -> Self {
+        match err {
+            RepoError::NotFound => Self::not_found(ErrorCode::NotFound, "Recurso no encontrado"),
+            RepoError::Duplicate => Self::conflict(ErrorCode::Conflict, "El recurso ya existe"),
+            RepoError::Database(err) => err.into(),
+        }
+    }
+}
 
 /// Enum que encapsula distintos tipos de respuesta de la aplicación.
-
+///
 /// `T` es el tipo de dato que se devolverá en caso de éxito.
+#[allow(dead_code)]
 #[derive(Serialize, Debug, Display)]
 pub enum AppResponse<T>
 where
@@ -86,12 +374,12 @@ where
 {
     /// Respuesta exitosa (200 OK)
     Success(T),
-    /// Solicitud inválida (400 Bad Request)
-    Invalid(&'static str),
+    /// Solicitud inválida (400 Bad Request), con el código `BAD_REQUEST`
+    Invalid(String),
     /// Error interno del servidor (500)
-    
+    ///
     /// ⚠️ El mensaje no se envía al cliente, pero sí se registra en los logs.
-    InternalError(&'static str),
+    InternalError(String),
 }
 
 impl<T> AppResponse<T>
@@ -99,7 +387,7 @@ where
     T: Serialize,
 {
     /// Método que genera la respuesta real que se enviará al cliente.
-    
+    ///
     /// Ejemplos de uso:
     /// - `AppResponse::Success(...)`
     /// - `AppResponse::Invalid(...)`
@@ -109,8 +397,9 @@ where
             Self::Success(data) => Ok(web::Json(OkModel {
                 success: true,
                 data,
+                pagination: None,
             })),
-            Self::Invalid(err) => Err(AppError::Invalid { err }),
+            Self::Invalid(err) => Err(AppError::invalid(ErrorCode::BadRequest, err)),
             Self::InternalError(err) => {
                 warn!("{}", err); // Se registra el error
                 Err(AppError::InternalError)
@@ -118,3 +407,33 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response_envuelve_los_datos_en_ok_model() {
+        let json = AppResponse::Success(7).response().unwrap();
+        assert!(json.success);
+        assert_eq!(json.data, 7);
+        assert!(json.pagination.is_none());
+    }
+
+    #[test]
+    fn response_convierte_los_errores_en_app_error() {
+        let Err(err) = AppResponse::<()>::Invalid(format!("Valor inválido: {}", 3)).response() else {
+            panic!("se esperaba un error");
+        };
+        assert_eq!(err.code(), ErrorCode::BadRequest);
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(err.message(), "Valor inválido: 3");
+
+        let Err(err) = AppResponse::<()>::InternalError("fallo al conectar".to_string()).response() else {
+            panic!("se esperaba un error");
+        };
+        assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        // El detalle solo va a los logs
+        assert_eq!(err.message(), "500 error interno del servidor");
+    }
+}
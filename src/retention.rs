@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+
+use crate::config::UsersConfig;
+use crate::repository::UserRepository;
+use crate::shutdown::Shutdown;
+
+/// Tarea periódica que borra definitivamente los usuarios eliminados hace más de
+/// `users.retention_days`.
+///
+/// Al apagar termina la purga en curso antes de salir.
+pub fn spawn_purge(shutdown: &Shutdown, repo: Arc<dyn UserRepository>, config: &UsersConfig) {
+    let retention = config.retention();
+    let purge_interval = config.purge_interval();
+
+    shutdown.spawn_worker("users-purge", |mut stop| async move {
+        let mut interval = actix_web::rt::time::interval(purge_interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = stop.stopped() => break,
+            }
+            match repo.purge(Utc::now() - retention).await {
+                Ok(0) => {}
+                Ok(purged) => log::info!("{} usuarios eliminados borrados definitivamente", purged),
+                Err(e) => log::warn!("No se pudieron purgar los usuarios eliminados: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,128 @@
+use std::{fs, io};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::JwtConfig;
+use crate::models::TokenResponse;
+use crate::repository::{RefreshToken, TokenRepository};
+use crate::response::AppError;
+
+/// Claims de los access tokens emitidos; `require_auth` los valida.
+#[derive(Serialize)]
+struct AccessClaims<'a> {
+    sub: String,
+    iat: i64,
+    exp: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iss: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aud: Option<&'a str>,
+}
+
+/// Emisor de access tokens (JWT) y refresh tokens (opacos, rotados en cada uso).
+pub struct TokenIssuer {
+    header: Header,
+    key: EncodingKey,
+    issuer: Option<String>,
+    audience: Option<String>,
+    access_ttl: Duration,
+    refresh_ttl: Duration,
+}
+
+impl TokenIssuer {
+    /// Carga la clave de firma: `private_key` (RS256, con `key_id` como `kid`) o, si no
+    /// hay, `secret` (HS256).
+    ///
+    /// Devuelve `None` si no hay clave: la API solo acepta tokens de un emisor externo.
+    pub fn from_config(config: &JwtConfig) -> io::Result<Option<Self>> {
+        let (mut header, key) = if let Some(path) = &config.private_key {
+            let pem = fs::read(path)?;
+            let key = EncodingKey::from_rsa_pem(&pem)
+                .map_err(|e| io::Error::other(format!("Clave inválida en {}: {}", path.display(), e)))?;
+            (Header::new(Algorithm::RS256), key)
+        } else if let Some(secret) = &config.secret {
+            (Header::new(Algorithm::HS256), EncodingKey::from_secret(secret.as_bytes()))
+        } else {
+            return Ok(None);
+        };
+        header.kid = config.key_id.clone();
+
+        Ok(Some(Self {
+            header,
+            key,
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+            access_ttl: ttl("access_ttl_secs", config.access_ttl_secs)?,
+            refresh_ttl: ttl("refresh_ttl_secs", config.refresh_ttl_secs)?,
+        }))
+    }
+
+    /// Emisor HS256 con `secret` y la validez por defecto (para las pruebas).
+    #[cfg(test)]
+    pub fn from_secret(secret: &str) -> Self {
+        let config = JwtConfig { secret: Some(secret.to_string()), ..JwtConfig::default() };
+        Self::from_config(&config).unwrap().unwrap()
+    }
+
+    /// Emite un access token y un refresh token nuevos para `user_id`.
+    ///
+    /// `family` identifica la sesión: `None` al hacer login, la del token anterior al rotar.
+    pub async fn issue(
+        &self,
+        tokens: &dyn TokenRepository,
+        user_id: i32,
+        family: Option<String>,
+    ) -> Result<TokenResponse, AppError> {
+        let now = Utc::now();
+        let claims = AccessClaims {
+            sub: user_id.to_string(),
+            iat: now.timestamp(),
+            exp: (now + self.access_ttl).timestamp(),
+            iss: self.issuer.as_deref(),
+            aud: self.audience.as_deref(),
+        };
+        let access_token = encode(&self.header, &claims, &self.key).map_err(|e| {
+            log::error!("Error al firmar el access token: {}", e);
+            AppError::InternalError
+        })?;
+
+        let refresh_token = random_token();
+        let stored = RefreshToken {
+            user_id,
+            family: family.unwrap_or_else(random_token),
+            expires_at: now + self.refresh_ttl,
+        };
+        tokens.insert(&hash_token(&refresh_token), &stored).await?;
+
+        Ok(TokenResponse {
+            access_token,
+            token_type: "Bearer",
+            expires_in: self.access_ttl.num_seconds(),
+            refresh_token,
+        })
+    }
+}
+
+fn ttl(key: &str, secs: u64) -> io::Result<Duration> {
+    i64::try_from(secs)
+        .ok()
+        .and_then(Duration::try_seconds)
+        .ok_or_else(|| io::Error::other(format!("jwt.{} es demasiado grande: {}", key, secs)))
+}
+
+/// 32 bytes aleatorios en base64url.
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// SHA-256 de un refresh token o API key: en la base de datos nunca se guarda el valor en claro.
+pub fn hash_token(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
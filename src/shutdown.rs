@@ -0,0 +1,146 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServerHandle, ServiceRequest, ServiceResponse},
+    http::ConnectionType,
+    middleware::Next,
+    web, Error,
+};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Estado compartido del ciclo de vida del proceso.
+///
+/// Al recibir `SIGTERM` (o Ctrl+C) el servicio deja de estar listo, espera
+/// `drain_delay` para que el balanceador lo retire, deja de aceptar conexiones,
+/// termina las peticiones en curso y avisa a los workers en segundo plano.
+pub struct Shutdown {
+    ready: AtomicBool,
+    /// Pasa a `true` cuando los workers deben terminar
+    stopping: watch::Sender<bool>,
+    workers: Mutex<Vec<(&'static str, JoinHandle<()>)>>,
+}
+
+/// Aviso de apagado que recibe cada worker.
+#[derive(Clone)]
+pub struct StopSignal(watch::Receiver<bool>);
+
+impl StopSignal {
+    /// Se completa cuando empieza el apagado; un worker lo combina con su espera
+    /// entre trabajos para no interrumpir el que está en curso.
+    pub async fn stopped(&mut self) {
+        // Si el emisor desaparece también hay que terminar
+        let _ = self.0.wait_for(|stopping| *stopping).await;
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            ready: AtomicBool::new(true),
+            stopping: watch::channel(false).0,
+            workers: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// `false` desde que se recibe la señal de apagado.
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    /// Lanza un worker en segundo plano que se esperará al apagar.
+    pub fn spawn_worker<F, Fut>(&self, name: &'static str, worker: F)
+    where
+        F: FnOnce(StopSignal) -> Fut,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let handle = actix_web::rt::spawn(worker(StopSignal(self.stopping.subscribe())));
+        self.workers.lock().unwrap_or_else(|e| e.into_inner()).push((name, handle));
+    }
+
+    /// Espera la señal de apagado y para el servidor de forma ordenada.
+    ///
+    /// Una segunda señal durante la espera para el servidor sin esperar a las peticiones en curso.
+    pub async fn on_signal(self: Arc<Self>, server: ServerHandle, drain_delay: Duration) {
+        let signal = wait_for_signal().await;
+        log::info!("Señal {} recibida: apagando el servidor", signal);
+        self.ready.store(false, Ordering::Relaxed);
+
+        let graceful = async {
+            if !drain_delay.is_zero() {
+                log::info!("Esperando {}s antes de dejar de aceptar conexiones", drain_delay.as_secs());
+                actix_web::rt::time::sleep(drain_delay).await;
+            }
+            server.stop(true).await;
+        };
+        tokio::select! {
+            _ = graceful => {}
+            signal = wait_for_signal() => {
+                log::warn!("Señal {} recibida de nuevo: apagado inmediato", signal);
+                server.stop(false).await;
+            }
+        }
+    }
+
+    /// Avisa a los workers y espera a que terminen su trabajo actual, como mucho `timeout`.
+    pub async fn stop_workers(&self, timeout: Duration) {
+        self.ready.store(false, Ordering::Relaxed);
+        self.stopping.send_replace(true);
+
+        let workers = std::mem::take(&mut *self.workers.lock().unwrap_or_else(|e| e.into_inner()));
+        for (name, handle) in workers {
+            let abort = handle.abort_handle();
+            match tokio::time::timeout(timeout, handle).await {
+                Ok(Ok(())) => log::debug!("Worker {} detenido", name),
+                Ok(Err(e)) => log::error!("El worker {} terminó con error: {}", name, e),
+                Err(_) => {
+                    log::warn!("El worker {} no terminó en {}s; se cancela", name, timeout.as_secs());
+                    abort.abort();
+                }
+            }
+        }
+    }
+}
+
+/// Espera `SIGTERM` (lo que envían los orquestadores) o Ctrl+C y devuelve su nombre.
+async fn wait_for_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => tokio::select! {
+                _ = sigterm.recv() => "SIGTERM",
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+            },
+            Err(e) => {
+                log::warn!("No se pudo escuchar SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl+C"
+    }
+}
+
+/// Middleware que, durante el apagado, pide al cliente cerrar la conexión tras la
+/// respuesta para que las siguientes peticiones vayan a otra instancia.
+pub async fn close_when_draining(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let shutdown = req.app_data::<web::Data<Shutdown>>().cloned();
+    let mut res = next.call(req).await?;
+    if shutdown.is_some_and(|shutdown| !shutdown.is_ready()) {
+        res.response_mut().head_mut().set_connection_type(ConnectionType::Close);
+    }
+    Ok(res)
+}
@@ -0,0 +1,250 @@
+use std::fs::{File, OpenOptions};
+use std::future::{self, Future};
+use std::io::{self, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::HeaderMap,
+    middleware::Next,
+    Error,
+};
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::{FutureExt, SpanKind, Status, TraceContextExt, TraceError, Tracer};
+use opentelemetry::{global, Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use serde_json::{json, Map, Value};
+
+use crate::config::{TraceExporter, TracingConfig};
+use crate::repository::{RepoError, RepoResult};
+use crate::request_id;
+
+/// Nombre del tracer de la aplicación.
+const TRACER: &str = "api";
+
+/// Instala el proveedor de trazas global según `[tracing]`.
+///
+/// Sin exportador las trazas son no-ops, pero el contexto `traceparent` recibido se
+/// sigue propagando a los logs. Devuelve el proveedor para vaciarlo al apagar.
+pub fn init(config: &TracingConfig) -> io::Result<Option<TracerProvider>> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let builder = TracerProvider::builder()
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio))))
+        .with_resource(Resource::new([KeyValue::new("service.name", config.service_name.clone())]));
+    let provider = match config.exporter {
+        TraceExporter::None => return Ok(None),
+        TraceExporter::Otlp => {
+            let mut exporter = opentelemetry_otlp::SpanExporter::builder().with_http();
+            // Sin `endpoint` se usa `OTEL_EXPORTER_OTLP_ENDPOINT` o el colector local
+            if let Some(endpoint) = &config.endpoint {
+                exporter = exporter.with_endpoint(endpoint);
+            }
+            let exporter = exporter.build().map_err(trace_error)?;
+            // actix usa runtimes de un solo hilo: el exportador necesita el suyo propio
+            builder.with_batch_exporter(exporter, runtime::TokioCurrentThread).build()
+        }
+        TraceExporter::Stdout => builder.with_simple_exporter(JsonLinesExporter::stdout()).build(),
+        TraceExporter::File => {
+            // `Config::validate` ya exige `tracing.file` con este exportador
+            let path = config.file.as_deref().unwrap_or(Path::new("spans.jsonl"));
+            let file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| {
+                io::Error::new(e.kind(), format!("No se pudo abrir {}: {}", path.display(), e))
+            })?;
+            builder.with_simple_exporter(JsonLinesExporter::file(file)).build()
+        }
+    };
+
+    global::set_tracer_provider(provider.clone());
+    Ok(Some(provider))
+}
+
+/// Envía las trazas pendientes y cierra el exportador.
+pub async fn shutdown(provider: TracerProvider) {
+    // `shutdown` bloquea hasta vaciar el lote, así que no puede correr en el runtime de actix
+    match actix_web::rt::task::spawn_blocking(move || provider.shutdown()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => log::warn!("No se pudieron enviar las últimas trazas: {}", e),
+        Err(e) => log::warn!("No se pudo cerrar el exportador de trazas: {}", e),
+    }
+}
+
+fn trace_error(err: TraceError) -> io::Error {
+    io::Error::other(format!("No se pudo crear el exportador de trazas: {}", err))
+}
+
+/// Identificador de la traza en curso, para correlacionar los logs.
+pub fn current_trace_id() -> Option<String> {
+    let context = Context::current();
+    let span = context.span();
+    let span_context = span.span_context();
+    span_context.is_valid().then(|| span_context.trace_id().to_string())
+}
+
+/// Middleware que abre un span `SERVER` por petición, continuando la traza del
+/// `traceparent` recibido, y lo mantiene como contexto actual mientras se atiende.
+pub async fn trace_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let parent = global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(req.headers())));
+    let method = req.method().to_string();
+
+    let mut attributes = vec![
+        KeyValue::new("http.request.method", method.clone()),
+        KeyValue::new("url.path", req.path().to_string()),
+    ];
+    if let Some(id) = request_id::current() {
+        attributes.push(KeyValue::new("http.request.id", id));
+    }
+    let tracer = global::tracer(TRACER);
+    let span = tracer
+        .span_builder(method.clone())
+        .with_kind(SpanKind::Server)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, &parent);
+    let context = parent.with_span(span);
+
+    let res = next.call(req).with_context(context.clone()).await;
+
+    let span = context.span();
+    match &res {
+        Ok(res) => {
+            let status = res.status();
+            // El nombre lleva la ruta, no la URL, para agrupar las peticiones a `/users/{id}`
+            if let Some(route) = res.request().match_pattern() {
+                span.update_name(format!("{} {}", method, route));
+                span.set_attribute(KeyValue::new("http.route", route));
+            }
+            span.set_attribute(KeyValue::new("http.response.status_code", i64::from(status.as_u16())));
+            if status.is_server_error() {
+                span.set_status(Status::error(status.to_string()));
+            }
+        }
+        Err(e) => span.set_status(Status::error(e.to_string())),
+    }
+    span.end();
+
+    res
+}
+
+/// Ejecuta una operación de repositorio dentro de un span `CLIENT` hijo del actual.
+///
+/// `NotFound` y `Duplicate` son respuestas normales; solo los errores del motor marcan el span.
+pub async fn trace_query<T>(
+    repository: &str,
+    operation: &str,
+    query: impl Future<Output = RepoResult<T>>,
+) -> RepoResult<T> {
+    let tracer = global::tracer(TRACER);
+    let span = tracer
+        .span_builder(format!("{}.{}", repository, operation))
+        .with_kind(SpanKind::Client)
+        .with_attributes([
+            KeyValue::new("db.collection.name", repository.to_string()),
+            KeyValue::new("db.operation.name", operation.to_string()),
+        ])
+        .start(&tracer);
+    let context = Context::current_with_span(span);
+
+    let result = query.with_context(context.clone()).await;
+    let span = context.span();
+    if let Err(e @ RepoError::Database(_)) = &result {
+        span.set_status(Status::error(e.to_string()));
+    }
+    span.end();
+    result
+}
+
+/// Lee `traceparent`/`tracestate` de las cabeceras de actix.
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Exportador que escribe un span JSON por línea, para desarrollo y pruebas sin colector.
+#[derive(Debug)]
+struct JsonLinesExporter {
+    /// `None` para la salida estándar
+    file: Option<File>,
+}
+
+impl JsonLinesExporter {
+    fn stdout() -> Self {
+        Self { file: None }
+    }
+
+    fn file(file: File) -> Self {
+        Self { file: Some(file) }
+    }
+
+    fn write(&mut self, batch: &[SpanData]) -> io::Result<()> {
+        let mut out = Vec::new();
+        for span in batch {
+            serde_json::to_writer(&mut out, &span_json(span)).map_err(io::Error::other)?;
+            out.push(b'\n');
+        }
+        match &mut self.file {
+            Some(file) => file.write_all(&out),
+            None => io::stdout().lock().write_all(&out),
+        }
+    }
+}
+
+impl SpanExporter for JsonLinesExporter {
+    fn export(&mut self, batch: Vec<SpanData>) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        let result = self.write(&batch).map_err(|e| TraceError::Other(Box::new(e)));
+        Box::pin(future::ready(result))
+    }
+}
+
+fn span_json(span: &SpanData) -> Value {
+    let attributes: Map<String, Value> = span
+        .attributes
+        .iter()
+        .map(|attribute| {
+            let value = match &attribute.value {
+                opentelemetry::Value::Bool(flag) => json!(flag),
+                opentelemetry::Value::I64(number) => json!(number),
+                opentelemetry::Value::F64(number) => json!(number),
+                value => json!(value.to_string()),
+            };
+            (attribute.key.to_string(), value)
+        })
+        .collect();
+    let status = match &span.status {
+        Status::Unset => json!("unset"),
+        Status::Ok => json!("ok"),
+        Status::Error { description } => json!({ "error": description }),
+    };
+
+    json!({
+        "name": span.name,
+        "kind": format!("{:?}", span.span_kind).to_lowercase(),
+        "trace_id": span.span_context.trace_id().to_string(),
+        "span_id": span.span_context.span_id().to_string(),
+        "parent_span_id": span.parent_span_id.to_string(),
+        "start_unix_nano": unix_nanos(span.start_time),
+        "end_unix_nano": unix_nanos(span.end_time),
+        "attributes": attributes,
+        "status": status,
+    })
+}
+
+fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_nanos() as u64).unwrap_or_default()
+}
@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use email_address::EmailAddress;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use crate::i18n::{self, Locale};
+use crate::response::AppError;
+
+/// Reglas incumplidas agrupadas por campo.
+pub type FieldErrors = BTreeMap<String, Vec<ValidationError>>;
+
+/// Errores por campo ya traducidos, en el formato que se envía al cliente.
+pub type FieldMessages = BTreeMap<String, Vec<String>>;
+
+/// Limpieza que se aplica a un modelo de entrada antes de validarlo.
+pub trait Normalize {
+    fn normalize(&mut self);
+}
+
+/// Normaliza y valida un modelo de entrada.
+///
+/// Se recogen todas las violaciones a la vez y se devuelven como `AppError::Validation`.
+pub fn validate<T: Validate + Normalize>(mut value: T) -> Result<T, AppError> {
+    value.normalize();
+    value
+        .validate()
+        .map_err(|errors| AppError::Validation { errors: field_errors(&errors) })?;
+    Ok(value)
+}
+
+fn field_errors(errors: &ValidationErrors) -> FieldErrors {
+    errors
+        .field_errors()
+        .into_iter()
+        .map(|(field, errors)| (field.to_string(), errors.clone()))
+        .collect()
+}
+
+/// Traduce los errores por campo al idioma pedido.
+pub fn messages(errors: &FieldErrors, locale: Locale) -> FieldMessages {
+    errors
+        .iter()
+        .map(|(field, errors)| {
+            let messages = errors.iter().map(|e| i18n::violation_message(e, locale)).collect();
+            (field.clone(), messages)
+        })
+        .collect()
+}
+
+/// Quita los espacios al principio y al final.
+pub fn trim(value: &mut String) {
+    let trimmed = value.trim();
+    if trimmed.len() != value.len() {
+        *value = trimmed.to_string();
+    }
+}
+
+/// Quita espacios y pasa el dominio a minúsculas (la parte local se respeta, RFC 5321).
+pub fn normalize_email(value: &mut String) {
+    trim(value);
+    if let Some((local, domain)) = value.rsplit_once('@') {
+        *value = format!("{}@{}", local, domain.to_lowercase());
+    }
+}
+
+/// Sintaxis de email según RFC 5322 (`addr-spec`).
+///
+/// Un valor vacío se deja pasar: lo reporta la regla de longitud.
+pub fn email(value: &str) -> Result<(), ValidationError> {
+    if value.is_empty() || EmailAddress::is_valid(value) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("email"))
+    }
+}
+
+/// Rechaza caracteres de control (saltos de línea, tabuladores, etc.).
+pub fn no_control_chars(value: &str) -> Result<(), ValidationError> {
+    if value.chars().any(char::is_control) {
+        Err(ValidationError::new("control_chars"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Exige una fecha posterior al momento actual.
+pub fn in_future(value: &DateTime<Utc>) -> Result<(), ValidationError> {
+    if *value > Utc::now() {
+        Ok(())
+    } else {
+        Err(ValidationError::new("future"))
+    }
+}
+
+/// Exige al menos un elemento en una lista.
+pub fn not_empty<T>(value: &[T]) -> Result<(), ValidationError> {
+    if value.is_empty() {
+        Err(ValidationError::new("not_empty"))
+    } else {
+        Ok(())
+    }
+}